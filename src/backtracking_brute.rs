@@ -1,50 +1,359 @@
 // Goal 6: Parallelize
 
-use std::collections::HashMap;
-use std::io::prelude::*;
-use itertools::Itertools;
 use rayon::prelude::*;
 
+use crate::checkpoint::Checkpoint;
+use crate::compat_index::*;
+use crate::constraints::Constraints;
+use crate::options::Options;
+use crate::shard::owns_branch;
+use crate::simd_filter::filter_disjoint;
 use crate::word_reprs::*;
 
-pub fn backtracking_brute(words: Vec<String>) {
+pub fn backtracking_brute(words: Vec<String>, options: &Options) {
     let (reprs, keys) = build_word_representations(&words);
-    aux(0, [0; 5], 0, 0, &keys, &reprs);
+    backtracking_brute_keys(&keys, options, &|combo| print_combo(combo, &reprs));
 }
 
-pub fn backtracking_brute_parallelized(words: Vec<String>) {
+pub fn backtracking_brute_parallelized(words: Vec<String>, options: &Options) {
     let (reprs, keys) = build_word_representations(&words);
-    (&keys).into_iter().enumerate().collect::<Vec<_>>()
-    .par_chunks(keys.len() / 24)
-    .for_each(|chunk| {
-        for (pos, key) in chunk {
-            aux(1, [**key, 0, 0, 0, 0], **key, *pos, &keys, &reprs);
-        }
-    });
+    backtracking_brute_parallelized_keys(&keys, options, &|combo| print_combo(combo, &reprs));
+}
+
+pub fn backtracking_brute_simd(words: Vec<String>, options: &Options) {
+    let (reprs, keys) = build_word_representations(&words);
+    backtracking_brute_simd_keys(&keys, options, &|combo| print_combo(combo, &reprs));
 }
 
-fn aux(depth: u8, combo: [u32; 5], combo_repr: u32, pos: usize, keys: &Vec<u32>,
-       reprs: &HashMap<u32, Vec<&str>>) {
+/// Candidates for each next word come from intersecting the `CompatIndex` rows of the words
+/// already chosen, instead of scanning all later keys.
+pub fn backtracking_brute_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let (combo, depth, combo_repr) = constraints.seed();
+    let keys = &constraints.free_keys(keys);
+    let emit = &constraints.sink(emit);
     if depth == 5 {
-        combo_pretty_print(combo, reprs);
+        if owns_branch(options, 0) && constraints.can_complete(combo_repr, []) {
+            emit(combo);
+        }
         return;
     }
+    let index = CompatIndex::build(keys);
+    if options.verbose {
+        index.report();
+    }
+    let mut buffers = vec![vec![0u64; index.words_per_row()]; 3];
+    // Each first key is a top-level branch; the free keys all fit with the seeded combo
+    for pos in (0..keys.len()).filter(|pos| owns_branch(options, *pos)) {
+        let mut new_combo = combo;
+        new_combo[depth] = keys[pos];
+        aux(
+            depth + 1,
+            new_combo,
+            combo_repr | keys[pos],
+            index.row(pos),
+            pos / 64,
+            &mut buffers,
+            keys,
+            &index,
+            &constraints,
+            emit,
+        );
+    }
+}
 
-    let mut new_combo: [u32; 5] = combo.clone();
-    for (pos2, key) in keys.iter().skip(pos).enumerate() {
-        if key & combo_repr != 0 {
-            continue;
+/// Forks the search tree with `rayon::join` over the first two words, so that work stealing can
+/// balance the early keys (which have much larger subtrees) against the late ones.
+/// With --resume, each first word is a checkpointed branch.
+pub fn backtracking_brute_parallelized_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let (combo, depth, combo_repr) = constraints.seed();
+    let keys = constraints.free_keys(keys);
+    let emit = &constraints.sink(emit);
+    if depth == 5 {
+        if owns_branch(options, 0) && constraints.can_complete(combo_repr, []) {
+            emit(combo);
         }
-        new_combo[depth as usize] = *key;
+        return;
+    }
+    let index = CompatIndex::build(&keys);
+    if options.verbose {
+        index.report();
+    }
+    // Every free key fits with the included ones
+    let candidates = index.all();
+    let positions: Vec<usize> = (0..keys.len())
+        .filter(|pos| owns_branch(options, *pos))
+        .collect();
+    match Checkpoint::open(options, "brute_par", &keys) {
+        Some(checkpoint) => positions
+            .par_iter()
+            .filter(|pos| !checkpoint.is_done(**pos))
+            .for_each(|&pos| {
+                checkpoint.run_branch(pos, emit, |sink| {
+                    fork(
+                        depth,
+                        combo,
+                        combo_repr,
+                        &[pos],
+                        &candidates,
+                        &keys,
+                        &index,
+                        &constraints,
+                        sink,
+                    )
+                });
+            }),
+        None => fork(
+            depth,
+            combo,
+            combo_repr,
+            &positions,
+            &candidates,
+            &keys,
+            &index,
+            &constraints,
+            emit,
+        ),
+    }
+}
+
+// Depth down to which the search is forked; below it each branch runs `aux` sequentially
+const FORK_DEPTH: usize = 2;
+
+#[allow(clippy::too_many_arguments)]
+fn fork(
+    depth: usize,
+    combo: [u32; 5],
+    combo_repr: u32,
+    positions: &[usize],
+    candidates: &[u64],
+    keys: &[u32],
+    index: &CompatIndex,
+    constraints: &Constraints,
+    emit: &ComboSink,
+) {
+    if positions.len() > 1 {
+        let (left, right) = positions.split_at(positions.len() / 2);
+        rayon::join(
+            || {
+                fork(
+                    depth,
+                    combo,
+                    combo_repr,
+                    left,
+                    candidates,
+                    keys,
+                    index,
+                    constraints,
+                    emit,
+                )
+            },
+            || {
+                fork(
+                    depth,
+                    combo,
+                    combo_repr,
+                    right,
+                    candidates,
+                    keys,
+                    index,
+                    constraints,
+                    emit,
+                )
+            },
+        );
+        return;
+    }
+
+    for &pos in positions {
+        let key = keys[pos];
+        let mut new_combo = combo;
+        new_combo[depth] = key;
         let new_combo_repr = combo_repr | key;
-        let new_pos = pos + pos2;
-        aux(depth + 1, new_combo, new_combo_repr, new_pos, keys, reprs);
+        let mut next = vec![0u64; index.words_per_row()];
+        intersect_rows(candidates, index.row(pos), pos / 64, &mut next);
+        if depth + 1 < FORK_DEPTH {
+            if !constraints.can_complete(
+                new_combo_repr,
+                set_bits(&next, pos / 64).map(|pos2| keys[pos2]),
+            ) {
+                continue;
+            }
+            let next_positions: Vec<usize> = set_bits(&next, pos / 64).collect();
+            fork(
+                depth + 1,
+                new_combo,
+                new_combo_repr,
+                &next_positions,
+                &next,
+                keys,
+                index,
+                constraints,
+                emit,
+            );
+        } else {
+            let mut buffers = vec![vec![0u64; index.words_per_row()]; 3];
+            aux(
+                depth + 1,
+                new_combo,
+                new_combo_repr,
+                &next,
+                pos / 64,
+                &mut buffers,
+                keys,
+                index,
+                constraints,
+                emit,
+            );
+        }
     }
 }
 
-fn combo_pretty_print(combo: [u32; 5], reprs: &HashMap<u32, Vec<&str>>) {
-    for word_combo in combo.iter().map(|key| reprs.get(key).unwrap()).multi_cartesian_product() {
-        let word_combo = word_combo.into_iter().fold(String::new(), |mut acc, word| { acc.push_str(word); acc.push_str(" "); acc });
-        let _ = writeln!(std::io::stdout(), "{}", word_combo);
+/// Same search as `backtracking_brute_keys`, but every level keeps only the keys still disjoint
+/// with the combo, filtered in batches by `filter_disjoint` instead of read off the index.
+pub fn backtracking_brute_simd_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let (combo, depth, combo_repr) = constraints.seed();
+    let emit = &constraints.sink(emit);
+    if depth == 5 {
+        if constraints.can_complete(combo_repr, []) {
+            emit(combo);
+        }
+        return;
+    }
+    let mut buffers: Vec<Vec<u32>> = vec![Vec::new(); 4];
+    aux_filtered(
+        depth,
+        combo,
+        combo_repr,
+        &constraints.free_keys(keys),
+        &mut buffers,
+        &constraints,
+        emit,
+    );
+}
+
+fn aux_filtered(
+    depth: usize,
+    combo: [u32; 5],
+    combo_repr: u32,
+    candidates: &[u32],
+    buffers: &mut [Vec<u32>],
+    constraints: &Constraints,
+    emit: &ComboSink,
+) {
+    if !constraints.can_complete(combo_repr, candidates.iter().copied()) {
+        return;
+    }
+    if depth == 4 {
+        for key in candidates {
+            if constraints.can_complete(combo_repr | key, []) {
+                let mut new_combo = combo;
+                new_combo[depth] = *key;
+                emit(new_combo);
+            }
+        }
+        return;
+    }
+
+    let (next, rest) = buffers.split_first_mut().unwrap();
+    for (pos, key) in candidates.iter().enumerate() {
+        let mut new_combo = combo;
+        new_combo[depth] = *key;
+        next.clear();
+        filter_disjoint(&candidates[pos + 1..], *key, next);
+        aux_filtered(
+            depth + 1,
+            new_combo,
+            combo_repr | key,
+            next,
+            rest,
+            constraints,
+            emit,
+        );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn aux(
+    depth: usize,
+    combo: [u32; 5],
+    combo_repr: u32,
+    candidates: &[u64],
+    first_word: usize,
+    buffers: &mut [Vec<u64>],
+    keys: &[u32],
+    index: &CompatIndex,
+    constraints: &Constraints,
+    emit: &ComboSink,
+) {
+    if depth == 5 {
+        if constraints.can_complete(combo_repr, []) {
+            emit(combo);
+        }
+        return;
+    }
+    if !constraints.can_complete(
+        combo_repr,
+        set_bits(candidates, first_word).map(|pos| keys[pos]),
+    ) {
+        return;
+    }
+    if depth == 4 {
+        for pos in set_bits(candidates, first_word) {
+            if constraints.can_complete(combo_repr | keys[pos], []) {
+                let mut new_combo = combo;
+                new_combo[depth] = keys[pos];
+                emit(new_combo);
+            }
+        }
+        return;
+    }
+
+    let (next, rest) = buffers.split_first_mut().unwrap();
+    for pos in set_bits(candidates, first_word) {
+        let mut new_combo = combo;
+        new_combo[depth] = keys[pos];
+        // The row of pos has no bits before pos, so neither does the intersection
+        intersect_rows(candidates, index.row(pos), pos / 64, next);
+        aux(
+            depth + 1,
+            new_combo,
+            combo_repr | keys[pos],
+            next,
+            pos / 64,
+            rest,
+            keys,
+            index,
+            constraints,
+            emit,
+        );
+    }
+}
+
+#[test]
+fn test_parallelized_matches_sequential() {
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    assert!(keys.len() < 24);
+    let expected = collect_sorted(backtracking_brute_keys, &keys);
+    assert!(!expected.is_empty());
+    assert_eq!(
+        collect_sorted(backtracking_brute_parallelized_keys, &keys),
+        expected
+    );
+
+    for n in 0..3 {
+        assert!(collect_sorted(backtracking_brute_parallelized_keys, &keys[..n]).is_empty());
     }
 }
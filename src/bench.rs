@@ -0,0 +1,51 @@
+// Times solvers on the same word list without printing their solutions.
+
+use prettytable::{Cell, Row, Table};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use crate::options::Options;
+use crate::word_reprs::*;
+use crate::{find_solver, SOLVERS};
+
+pub fn bench(words: Vec<String>, solver_names: &[String], options: &Options) {
+    let options = &Options {
+        verbose: true,
+        ..options.clone()
+    };
+    let (reprs, keys) = build_word_representations(&words);
+    let names: Vec<&str> = if solver_names.is_empty() {
+        SOLVERS.iter().map(|(name, _)| *name).collect()
+    } else {
+        solver_names.iter().map(|s| s.as_str()).collect()
+    };
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("solver"),
+        Cell::new("combos"),
+        Cell::new("solutions"),
+        Cell::new("time [ms]"),
+    ]));
+    for name in names {
+        let solver = match find_solver(name) {
+            Some(solver) => solver,
+            None => panic!("unknown solver {}", name),
+        };
+        let combos = AtomicUsize::new(0);
+        let solutions = AtomicUsize::new(0);
+        let start = Instant::now();
+        solver(&keys, options, &|combo| {
+            combos.fetch_add(1, Ordering::Relaxed);
+            solutions.fetch_add(combo_word_count(&combo, &reprs), Ordering::Relaxed);
+        });
+        let elapsed = start.elapsed();
+        table.add_row(Row::new(vec![
+            Cell::new(name),
+            Cell::new(&combos.into_inner().to_string()),
+            Cell::new(&solutions.into_inner().to_string()),
+            Cell::new(&format!("{:.1}", elapsed.as_secs_f64() * 1000.0)),
+        ]));
+    }
+    table.printstd();
+}
@@ -0,0 +1,149 @@
+// Backtracking that always covers the rarest still-uncovered letter next.
+//
+// Keys are bucketed by their rarest letter. A solution covers 25 of the 26 letters, so at
+// every step the lowest (by rarity) uncovered letter must either be covered by a key from
+// its own bucket (all rarer letters are already taken, so that key's rarest letter is this
+// one), or be the single letter the solution leaves out.
+
+use itertools::Itertools;
+
+use crate::constraints::Constraints;
+use crate::options::Options;
+use crate::word_reprs::*;
+
+const LETTERS: usize = 26;
+// 26 letters, 5 words of 5 letters each
+const MAX_SKIPS: u8 = 1;
+
+pub fn brute_rare_first(words: Vec<String>, options: &Options) {
+    let (reprs, keys) = build_word_representations(&words);
+    brute_rare_first_keys(&keys, options, &|combo| print_combo(combo, &reprs));
+}
+
+/// The included words seed the combo, so their letters count as covered from the start, and
+/// only letters outside `Constraints::letters` may be the one left out.
+pub fn brute_rare_first_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let keys = constraints.free_keys(keys);
+    let order = letters_by_frequency(&keys);
+    let buckets = bucket_by_rarest_letter(&keys, &order);
+    let (combo, depth, combo_repr) = constraints.seed();
+    aux(
+        depth as u8,
+        combo,
+        combo_repr,
+        0,
+        0,
+        constraints.skippable(),
+        &order,
+        &buckets,
+        emit,
+    );
+}
+
+/// Letters (as bit indices) sorted from the rarest to the most common among `keys`.
+fn letters_by_frequency(keys: &[u32]) -> [u32; LETTERS] {
+    let mut freq = [0usize; LETTERS];
+    for key in keys {
+        for (letter, f) in freq.iter_mut().enumerate() {
+            if key & (1 << letter) != 0 {
+                *f += 1;
+            }
+        }
+    }
+    let mut order = [0u32; LETTERS];
+    for (i, letter) in (0..LETTERS as u32)
+        .sorted_by_key(|letter| (freq[*letter as usize], *letter))
+        .enumerate()
+    {
+        order[i] = letter;
+    }
+    order
+}
+
+/// buckets[i] holds the keys whose rarest letter is order[i]
+fn bucket_by_rarest_letter(keys: &[u32], order: &[u32; LETTERS]) -> Vec<Vec<u32>> {
+    let mut buckets: Vec<Vec<u32>> = vec![Vec::new(); LETTERS];
+    for &key in keys {
+        if let Some(i) = order.iter().position(|letter| key & (1 << letter) != 0) {
+            buckets[i].push(key);
+        }
+    }
+    buckets
+}
+
+#[allow(clippy::too_many_arguments)]
+fn aux(
+    depth: u8,
+    combo: [u32; 5],
+    combo_repr: u32,
+    letter_pos: usize,
+    skips: u8,
+    skippable: u32,
+    order: &[u32; LETTERS],
+    buckets: &[Vec<u32>],
+    emit: &ComboSink,
+) {
+    if depth == 5 {
+        emit(combo);
+        return;
+    }
+
+    // Find the rarest letter not covered yet
+    let mut pos = letter_pos;
+    while pos < LETTERS && combo_repr & (1 << order[pos]) != 0 {
+        pos += 1;
+    }
+    if pos == LETTERS {
+        return;
+    }
+
+    let mut new_combo: [u32; 5] = combo;
+    for key in buckets[pos].iter() {
+        if key & combo_repr != 0 {
+            continue;
+        }
+        new_combo[depth as usize] = *key;
+        aux(
+            depth + 1,
+            new_combo,
+            combo_repr | key,
+            pos + 1,
+            skips,
+            skippable,
+            order,
+            buckets,
+            emit,
+        );
+    }
+
+    // Leave this letter out of the solution
+    if skips < MAX_SKIPS && skippable & (1 << order[pos]) != 0 {
+        let skipped_repr = combo_repr | (1 << order[pos]);
+        aux(
+            depth,
+            combo,
+            skipped_repr,
+            pos + 1,
+            skips + 1,
+            skippable,
+            order,
+            buckets,
+            emit,
+        );
+    }
+}
+
+#[test]
+fn test_rare_first_matches_backtracking() {
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+
+    let expected = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    let found = collect_sorted(brute_rare_first_keys, &keys);
+    assert!(!expected.is_empty());
+    assert_eq!(found, expected);
+}
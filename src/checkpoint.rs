@@ -0,0 +1,251 @@
+// --resume: saves how far a search got, so an interrupted run can pick up from there.
+//
+// The checkpoint is a text file starting with a header that names the solver and fingerprints
+// its input, followed by records:
+//
+//   done <branch>              a top-level branch of brute_par or smart_brute_par is finished
+//   solution <row> <row> ...   a DLX solution found so far
+//   stack <index> ...          the DLX node to continue from (see `exact_cover_resumable`)
+//   finished                   the DLX search is over
+//
+// The branch solvers hold back each branch's solutions until it is finished, print them and only
+// then append its `done` record, so a resumed run never repeats a solution (short of being
+// killed between the two). DLX collects its solutions before printing them anyway; its state
+// is rewritten as a whole every SAVE_INTERVAL and when the search ends.
+
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::dancing_links_soa::DLMatrix;
+use crate::index::checksum;
+use crate::options::Options;
+use crate::word_reprs::ComboSink;
+
+/// The solvers that save their progress to --resume
+pub const RESUMABLE_SOLVERS: &[&str] = &["brute_par", "smart_brute_par", "dlx"];
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+// Nodes between looking at the clock
+const NODES_PER_CHECK: usize = 4096;
+
+#[derive(Debug, Default, PartialEq)]
+struct Progress {
+    done: HashSet<usize>,
+    solutions: Vec<Vec<u32>>,
+    stack: Vec<usize>,
+    finished: bool,
+}
+
+impl Progress {
+    fn parse(lines: &[&str]) -> Self {
+        let mut progress = Progress::default();
+        for line in lines {
+            let mut fields = line.split_whitespace();
+            let record = fields.next();
+            let values: Option<Vec<usize>> = fields.map(|field| field.parse().ok()).collect();
+            // A run killed mid-write may leave half a record behind
+            let Some(values) = values else {
+                continue;
+            };
+            match (record, values.as_slice()) {
+                (Some("done"), [branch]) => {
+                    progress.done.insert(*branch);
+                }
+                (Some("solution"), rows) => progress
+                    .solutions
+                    .push(rows.iter().map(|row| *row as u32).collect()),
+                (Some("stack"), stack) => progress.stack = stack.to_vec(),
+                (Some("finished"), []) => progress.finished = true,
+                _ => {}
+            }
+        }
+        progress
+    }
+}
+
+pub struct Checkpoint {
+    path: PathBuf,
+    header: String,
+    progress: Progress,
+    log: Mutex<File>,
+}
+
+impl Checkpoint {
+    /// Opens the --resume file for `solver` searching `keys`, creating it if needed. Panics if
+    /// the file was written for a different search.
+    pub fn open(options: &Options, solver: &str, keys: &[u32]) -> Option<Self> {
+        let path = options.resume.clone()?;
+        let mut fingerprint: Vec<u8> = keys.iter().flat_map(|key| key.to_le_bytes()).collect();
+        fingerprint.extend_from_slice(
+            format!(
+                "{:?} {:?} {} {:?} {:?}",
+                options.include,
+                options.exclude,
+                options.require_letters,
+                options.unused_letter,
+                options.shard
+            )
+            .as_bytes(),
+        );
+        let header = format!(
+            "five_letter_words checkpoint 1 {} {:016x}",
+            solver,
+            checksum(&fingerprint)
+        );
+
+        let progress = match fs::read_to_string(&path) {
+            Ok(text) => {
+                let lines: Vec<&str> = text.lines().collect();
+                if lines.first() != Some(&header.as_str()) {
+                    panic!(
+                        "{} is not a checkpoint of this search (expected {:?})",
+                        path.display(),
+                        header
+                    );
+                }
+                Progress::parse(&lines[1..])
+            }
+            Err(_) => {
+                if let Err(why) = fs::write(&path, format!("{}\n", header)) {
+                    panic!("couldn't create {}: {}", path.display(), why);
+                }
+                Progress::default()
+            }
+        };
+        let log = match OpenOptions::new().append(true).open(&path) {
+            Err(why) => panic!("couldn't open {}: {}", path.display(), why),
+            Ok(file) => file,
+        };
+        Some(Checkpoint {
+            path,
+            header,
+            progress,
+            log: Mutex::new(log),
+        })
+    }
+
+    pub fn is_done(&self, branch: usize) -> bool {
+        self.progress.done.contains(&branch)
+    }
+
+    /// Runs the search of one top-level branch and passes its solutions on once it's finished.
+    pub fn run_branch(&self, branch: usize, emit: &ComboSink, search: impl FnOnce(&ComboSink)) {
+        let found = Mutex::new(Vec::new());
+        search(&|combo| found.lock().unwrap().push(combo));
+        let mut log = self.log.lock().unwrap();
+        for combo in found.into_inner().unwrap() {
+            emit(combo);
+        }
+        let _ = std::io::stdout().flush();
+        if let Err(why) = writeln!(log, "done {}", branch) {
+            panic!("couldn't write {}: {}", self.path.display(), why);
+        }
+    }
+
+    /// `matrix.exact_cover()`, continuing from the saved node.
+    pub fn exact_cover(&self, matrix: &mut DLMatrix) -> Vec<Vec<u32>> {
+        if self.progress.finished {
+            return self.progress.solutions.clone();
+        }
+        let mut nodes = 0;
+        let mut last_save = Instant::now();
+        let solutions = matrix.exact_cover_resumable(
+            &self.progress.stack,
+            self.progress.solutions.clone(),
+            &mut |stack, solutions| {
+                nodes += 1;
+                if nodes % NODES_PER_CHECK == 0 && last_save.elapsed() >= SAVE_INTERVAL {
+                    self.save(solutions, Some(stack));
+                    last_save = Instant::now();
+                }
+            },
+        );
+        self.save(&solutions, None);
+        solutions
+    }
+
+    // Replaces the file, so that it never holds half a state
+    fn save(&self, solutions: &[Vec<u32>], stack: Option<&[usize]>) {
+        let mut text = format!("{}\n", self.header);
+        for solution in solutions {
+            let rows: Vec<String> = solution.iter().map(|row| row.to_string()).collect();
+            text.push_str(&format!("solution {}\n", rows.join(" ")));
+        }
+        match stack {
+            Some(stack) => {
+                let stack: Vec<String> = stack.iter().map(|i| i.to_string()).collect();
+                text.push_str(&format!("stack {}\n", stack.join(" ")));
+            }
+            None => text.push_str("finished\n"),
+        }
+        // Appended rather than replacing the extension, so that checkpoints differing only in
+        // their extension don't share a temporary file
+        let tmp = PathBuf::from(format!("{}.tmp", self.path.display()));
+        if let Err(why) = fs::write(&tmp, text).and_then(|_| fs::rename(&tmp, &self.path)) {
+            panic!("couldn't write {}: {}", self.path.display(), why);
+        }
+    }
+}
+
+#[test]
+fn test_parse_progress() {
+    let progress = Progress::parse(&["done 3", "solution 1 2", "stack 0 4", "done 7", "don"]);
+    assert_eq!(progress.done, HashSet::from([3, 7]));
+    assert_eq!(progress.solutions, vec![vec![1, 2]]);
+    assert_eq!(progress.stack, vec![0, 4]);
+    assert!(!progress.finished);
+    assert!(Progress::parse(&["finished"]).finished);
+}
+
+#[test]
+fn test_resume_skips_done_branches() {
+    use crate::backtracking_brute::*;
+    use crate::exact_cover::dlx_keys;
+    use crate::smart_brute::smart_brute_keys;
+    use crate::word_reprs::*;
+
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let expected = collect_sorted(backtracking_brute_keys, &keys);
+    let path = std::env::temp_dir().join(format!("flw-checkpoint-{}", std::process::id()));
+    let options = Options {
+        resume: Some(path.clone()),
+        ..Options::default()
+    };
+    let solvers: [(crate::SolverFn, bool); 3] = [
+        (backtracking_brute_parallelized_keys, true),
+        (smart_brute_keys, true),
+        (dlx_keys, false),
+    ];
+    for (solver, has_branches) in solvers {
+        let _ = fs::remove_file(&path);
+        assert_eq!(collect_sorted_with(solver, &keys, &options), expected);
+        if !has_branches {
+            // A finished DLX checkpoint gives back its solutions
+            assert_eq!(collect_sorted_with(solver, &keys, &options), expected);
+            continue;
+        }
+        assert!(collect_sorted_with(solver, &keys, &options).is_empty());
+
+        // Keep the first branch of every other one and resume: the branches are the position
+        // of a combo's lowest key
+        let text = fs::read_to_string(&path).unwrap();
+        let mut lines = text.lines();
+        let mut kept = vec![lines.next().unwrap().to_string()];
+        kept.extend(lines.step_by(2).map(|line| line.to_string()));
+        let done = Progress::parse(&kept.iter().map(|l| l.as_str()).collect::<Vec<_>>()).done;
+        fs::write(&path, kept.join("\n") + "\n").unwrap();
+        let remaining: Vec<[u32; 5]> = expected
+            .iter()
+            .filter(|combo| !done.contains(&keys.binary_search(&combo[0]).unwrap()))
+            .copied()
+            .collect();
+        assert!(remaining.len() < expected.len());
+        assert_eq!(collect_sorted_with(solver, &keys, &options), remaining);
+    }
+    fs::remove_file(&path).unwrap();
+}
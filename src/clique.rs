@@ -0,0 +1,397 @@
+// The problem as a graph: anagram classes are vertices, joined when their letters are disjoint,
+// and a solution is a 5-clique.
+//
+// The clique solver lists k-cliques the Chiba-Nishizeki way. Vertices are numbered in degeneracy
+// order (repeatedly taking out one of least remaining degree) and every edge points to the
+// vertex numbered later, so each clique is found exactly once, from its first vertex, and no
+// vertex has more out-neighbors than the graph's degeneracy. The candidates at each level are
+// the AND of the out-neighbor bitsets of the vertices chosen so far.
+//
+// `graph` writes the same graph out in DIMACS or GraphML, for running other clique finders on.
+
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Instant;
+
+use crate::compat_index::{intersect_rows, set_bits};
+use crate::constraints::Constraints;
+use crate::options::Options;
+use crate::simd_filter::filter_disjoint_positions;
+use crate::word_reprs::{build_word_representations, ComboSink};
+
+/// The letter-disjointness graph over `keys`, one bitset row of neighbors per vertex.
+pub struct DisjointGraph {
+    words_per_row: usize,
+    rows: Vec<u64>,
+}
+
+impl DisjointGraph {
+    pub fn build(keys: &[u32]) -> Self {
+        let words_per_row = keys.len().div_ceil(64);
+        let mut rows = vec![0u64; keys.len() * words_per_row];
+        let mut positions = Vec::new();
+        for (v, key) in keys.iter().enumerate() {
+            positions.clear();
+            filter_disjoint_positions(&keys[v + 1..], *key, &mut positions);
+            for offset in positions.iter() {
+                let u = v + 1 + *offset as usize;
+                rows[v * words_per_row + u / 64] |= 1 << (u % 64);
+                rows[u * words_per_row + v / 64] |= 1 << (v % 64);
+            }
+        }
+        DisjointGraph {
+            words_per_row,
+            rows,
+        }
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.rows.len().checked_div(self.words_per_row).unwrap_or(0)
+    }
+
+    pub fn row(&self, v: usize) -> &[u64] {
+        &self.rows[v * self.words_per_row..(v + 1) * self.words_per_row]
+    }
+
+    pub fn neighbors(&self, v: usize) -> impl Iterator<Item = usize> + '_ {
+        set_bits(self.row(v), 0)
+    }
+
+    pub fn degree(&self, v: usize) -> usize {
+        self.row(v)
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        (0..self.vertex_count())
+            .map(|v| self.degree(v))
+            .sum::<usize>()
+            / 2
+    }
+
+    /// The vertices in degeneracy order, and the degeneracy: the largest degree a vertex had
+    /// when it was taken out.
+    pub fn degeneracy_order(&self) -> (Vec<usize>, usize) {
+        let n = self.vertex_count();
+        let mut degrees: Vec<usize> = (0..n).map(|v| self.degree(v)).collect();
+        let mut buckets: Vec<Vec<usize>> = vec![Vec::new(); n.max(1)];
+        for (v, degree) in degrees.iter().enumerate() {
+            buckets[*degree].push(v);
+        }
+        let mut removed = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        let mut degeneracy = 0;
+        let mut lowest = 0;
+        while order.len() < n {
+            // Entries left behind when a vertex's degree dropped are skipped
+            let Some(v) = buckets[lowest].pop() else {
+                lowest += 1;
+                continue;
+            };
+            if removed[v] || degrees[v] != lowest {
+                continue;
+            }
+            removed[v] = true;
+            order.push(v);
+            degeneracy = degeneracy.max(lowest);
+            for u in self.neighbors(v) {
+                if !removed[u] {
+                    degrees[u] -= 1;
+                    buckets[degrees[u]].push(u);
+                }
+            }
+            lowest = lowest.saturating_sub(1);
+        }
+        (order, degeneracy)
+    }
+}
+
+/// The graph with its vertices renumbered in degeneracy order and only the edges to later
+/// vertices kept.
+struct Oriented {
+    /// Original vertex of each position in the order
+    vertices: Vec<usize>,
+    words_per_row: usize,
+    rows: Vec<u64>,
+}
+
+impl Oriented {
+    fn new(graph: &DisjointGraph) -> (Self, usize) {
+        let (vertices, degeneracy) = graph.degeneracy_order();
+        let mut rank = vec![0; vertices.len()];
+        for (i, v) in vertices.iter().enumerate() {
+            rank[*v] = i;
+        }
+        let words_per_row = graph.words_per_row;
+        let mut rows = vec![0u64; graph.rows.len()];
+        for (i, v) in vertices.iter().enumerate() {
+            for u in graph.neighbors(*v).map(|u| rank[u]).filter(|u| *u > i) {
+                rows[i * words_per_row + u / 64] |= 1 << (u % 64);
+            }
+        }
+        let oriented = Oriented {
+            vertices,
+            words_per_row,
+            rows,
+        };
+        (oriented, degeneracy)
+    }
+
+    fn row(&self, i: usize) -> &[u64] {
+        &self.rows[i * self.words_per_row..(i + 1) * self.words_per_row]
+    }
+
+    /// Extends `clique`, whose last vertex is at position `last` in the order, by the vertices in
+    /// `candidates` (all after `last`) to k-cliques, and passes them to `emit` as original
+    /// vertices.
+    fn list(
+        &self,
+        k: usize,
+        clique: &mut Vec<usize>,
+        last: usize,
+        candidates: &[u64],
+        buffers: &mut [Vec<u64>],
+        emit: &mut dyn FnMut(&[usize]),
+    ) {
+        for i in set_bits(candidates, last / 64) {
+            clique.push(self.vertices[i]);
+            if clique.len() == k {
+                emit(clique);
+            } else {
+                let (next, rest) = buffers.split_first_mut().unwrap();
+                intersect_rows(candidates, self.row(i), i / 64, next);
+                let count: usize = next[i / 64..].iter().map(|w| w.count_ones() as usize).sum();
+                if count >= k - clique.len() {
+                    self.list(k, clique, i, next, rest, emit);
+                }
+            }
+            clique.pop();
+        }
+    }
+}
+
+/// Every k-clique of `graph`, each as its vertices in some order, for k >= 1. Returns the
+/// degeneracy.
+pub fn k_cliques(graph: &DisjointGraph, k: usize, emit: &(dyn Fn(&[usize]) + Sync)) -> usize {
+    assert!(k >= 1, "cliques have at least one vertex");
+    let (oriented, degeneracy) = Oriented::new(graph);
+    (0..graph.vertex_count()).into_par_iter().for_each(|i| {
+        let mut buffers = vec![vec![0u64; oriented.words_per_row]; k.saturating_sub(1)];
+        let mut clique = vec![oriented.vertices[i]];
+        if k == 1 {
+            emit(&clique);
+            return;
+        }
+        oriented.list(k, &mut clique, i, oriented.row(i), &mut buffers, &mut |c| {
+            emit(c)
+        });
+    });
+    degeneracy
+}
+
+/// Solutions as the 5-cliques of the disjointness graph over the candidate keys.
+pub fn clique_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let keys = &constraints.candidate_keys(keys);
+    let start = Instant::now();
+    let graph = DisjointGraph::build(keys);
+    let build_time = start.elapsed();
+    let degeneracy = k_cliques(&graph, 5, &|clique| {
+        let mut combo = [0; 5];
+        for (key, v) in combo.iter_mut().zip(clique.iter()) {
+            *key = keys[*v];
+        }
+        // The clique listing knows nothing about letters, so required ones are checked here
+        if constraints.accepts(&combo) {
+            emit(combo);
+        }
+    });
+    if options.verbose {
+        eprintln!(
+            "disjointness graph: {} vertices, {} edges, degeneracy {}, built in {:.1} ms",
+            graph.vertex_count(),
+            graph.edge_count(),
+            degeneracy,
+            build_time.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_dimacs(
+    out: &mut impl Write,
+    classes: &[String],
+    graph: &DisjointGraph,
+) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "c five_letter_words letter-disjointness graph over anagram classes"
+    )?;
+    for (v, class) in classes.iter().enumerate() {
+        writeln!(out, "c vertex {} {}", v + 1, class)?;
+    }
+    writeln!(
+        out,
+        "p edge {} {}",
+        graph.vertex_count(),
+        graph.edge_count()
+    )?;
+    for v in 0..graph.vertex_count() {
+        for u in set_bits(graph.row(v), v / 64).filter(|u| *u > v) {
+            writeln!(out, "e {} {}", v + 1, u + 1)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_graphml(
+    out: &mut impl Write,
+    classes: &[String],
+    keys: &[u32],
+    graph: &DisjointGraph,
+) -> std::io::Result<()> {
+    writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        out,
+        "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">"
+    )?;
+    writeln!(
+        out,
+        "  <key id=\"words\" for=\"node\" attr.name=\"words\" attr.type=\"string\"/>"
+    )?;
+    writeln!(
+        out,
+        "  <key id=\"key\" for=\"node\" attr.name=\"key\" attr.type=\"int\"/>"
+    )?;
+    writeln!(out, "  <graph id=\"disjoint\" edgedefault=\"undirected\">")?;
+    for (v, class) in classes.iter().enumerate() {
+        writeln!(
+            out,
+            "    <node id=\"n{}\"><data key=\"words\">{}</data><data key=\"key\">{}</data></node>",
+            v,
+            xml_escape(class),
+            keys[v]
+        )?;
+    }
+    for v in 0..graph.vertex_count() {
+        for u in set_bits(graph.row(v), v / 64).filter(|u| *u > v) {
+            writeln!(out, "    <edge source=\"n{}\" target=\"n{}\"/>", v, u)?;
+        }
+    }
+    writeln!(out, "  </graph>")?;
+    writeln!(out, "</graphml>")
+}
+
+/// `graph <out> [dimacs|graphml]`: writes the disjointness graph, as GraphML if asked for or if
+/// `out` ends in .graphml and as DIMACS otherwise. Vertices are the anagram classes in key order.
+pub fn write_graph(words: &[String], args: &[String]) {
+    let out = match args.first() {
+        Some(out) => Path::new(out),
+        None => panic!("usage: <wordfile> graph <out> [dimacs|graphml]"),
+    };
+    let graphml = match args.get(1).map(|format| format.as_str()) {
+        Some("graphml") => true,
+        Some("dimacs") => false,
+        Some(format) => panic!(
+            "unknown graph format {} (expected dimacs or graphml)",
+            format
+        ),
+        None => out.extension().is_some_and(|ext| ext == "graphml"),
+    };
+    let (reprs, keys) = build_word_representations(words);
+    let classes: Vec<String> = keys.iter().map(|key| reprs[key].join("/")).collect();
+    let graph = DisjointGraph::build(&keys);
+    let result = File::create(out).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        if graphml {
+            write_graphml(&mut writer, &classes, &keys, &graph)?;
+        } else {
+            write_dimacs(&mut writer, &classes, &graph)?;
+        }
+        writer.flush()
+    });
+    if let Err(why) = result {
+        panic!("couldn't write {}: {}", out.display(), why);
+    }
+    eprintln!(
+        "graph: {} vertices, {} edges",
+        graph.vertex_count(),
+        graph.edge_count()
+    );
+}
+
+#[test]
+fn test_degeneracy_order() {
+    // Pairwise disjoint, so K5
+    let keys = [0b00011, 0b00100, 0b01000, 0b10000, 0b100000];
+    let graph = DisjointGraph::build(&keys);
+    assert_eq!(graph.edge_count(), 10);
+    let (mut order, degeneracy) = graph.degeneracy_order();
+    order.sort();
+    assert_eq!(order, vec![0, 1, 2, 3, 4]);
+    assert_eq!(degeneracy, 4);
+
+    let keys = [0b0011, 0b0110, 0b1100, 0b1000];
+    let graph = DisjointGraph::build(&keys);
+    // 0-2, 0-3, 1-3: a path, degeneracy 1
+    assert_eq!(graph.edge_count(), 3);
+    assert_eq!(graph.degeneracy_order().1, 1);
+}
+
+#[test]
+fn test_k_cliques_match_brute() {
+    use crate::word_reprs::*;
+    use itertools::Itertools;
+    use std::sync::Mutex;
+
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let graph = DisjointGraph::build(&keys);
+    for k in 1..=5 {
+        let found = Mutex::new(Vec::new());
+        k_cliques(&graph, k, &|clique| {
+            let mut clique = clique.to_vec();
+            clique.sort();
+            found.lock().unwrap().push(clique);
+        });
+        let mut found = found.into_inner().unwrap();
+        found.sort();
+        let expected: Vec<Vec<usize>> = (0..keys.len())
+            .combinations(k)
+            .filter(|set| {
+                set.iter()
+                    .tuple_combinations()
+                    .all(|(a, b)| keys[*a] & keys[*b] == 0)
+            })
+            .collect();
+        assert_eq!(found, expected, "k = {}", k);
+    }
+}
+
+#[test]
+fn test_write_dimacs() {
+    let keys = [0b0011, 0b0110, 0b1100, 0b1000];
+    let classes: Vec<String> = ["ab", "bc", "cd", "d"]
+        .iter()
+        .map(|c| c.to_string())
+        .collect();
+    let mut out = Vec::new();
+    write_dimacs(&mut out, &classes, &DisjointGraph::build(&keys)).unwrap();
+    let text = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = text.lines().filter(|line| !line.starts_with('c')).collect();
+    assert_eq!(lines, vec!["p edge 4 3", "e 1 3", "e 1 4", "e 2 4"]);
+}
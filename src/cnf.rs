@@ -0,0 +1,407 @@
+// export-cnf and import-model: the word packing problem as DIMACS CNF, for external SAT solvers
+// and model counters.
+//
+// Variables 1..=n are the words (duplicates dropped); the CNF's comments say which is which.
+// Every other variable is an auxiliary whose value follows from the words', so each model is one
+// word-level solution and a #SAT count is the number of solutions:
+//
+// - per letter, s_i = s_(i-1) | x_i over the words with that letter, and no x_i may join an
+//   s_(i-1) that is already true (at most one word per letter)
+// - a sequential counter r_(i,j) = "at least j of the first i words", with r_(n,K) true and
+//   no x_i after r_(i-1,K) (exactly K words)
+//
+// --include becomes unit clauses, --unused-letter drops its words, and --require-letters asks
+// for one of the words with each required letter.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io::prelude::*;
+use std::io::BufWriter;
+use std::path::Path;
+
+use crate::options::Options;
+use crate::word_reprs::get_repr;
+
+const DEFAULT_SET_SIZE: usize = 5;
+
+pub struct Cnf {
+    pub variables: usize,
+    pub clauses: Vec<Vec<i64>>,
+}
+
+impl Cnf {
+    fn variable(&mut self) -> i64 {
+        self.variables += 1;
+        self.variables as i64
+    }
+
+    /// v <-> (a | b)
+    fn define_or(&mut self, v: i64, a: i64, b: i64) {
+        self.clauses.push(vec![-a, v]);
+        self.clauses.push(vec![-b, v]);
+        self.clauses.push(vec![-v, a, b]);
+    }
+
+    /// v <-> (a | (b & c))
+    fn define_or_and(&mut self, v: i64, a: i64, b: i64, c: i64) {
+        self.clauses.push(vec![-a, v]);
+        self.clauses.push(vec![-b, -c, v]);
+        self.clauses.push(vec![-v, a, b]);
+        self.clauses.push(vec![-v, a, c]);
+    }
+
+    /// "Choose `size` of `words` with pairwise disjoint letters", word i being variable i + 1.
+    pub fn word_packing(words: &[String], size: usize, options: &Options) -> Self {
+        let mut cnf = Cnf {
+            variables: words.len(),
+            clauses: Vec::new(),
+        };
+        let reprs: Vec<u32> = words.iter().map(|word| get_repr(word)).collect();
+        let x = |i: usize| i as i64 + 1;
+
+        for letter in 0..26 {
+            let with_letter: Vec<usize> = (0..words.len())
+                .filter(|i| reprs[*i] & (1 << letter) != 0)
+                .collect();
+            if options.unused_letter == Some(letter) {
+                cnf.clauses.extend(with_letter.iter().map(|i| vec![-x(*i)]));
+                continue;
+            }
+            if options.require_letters & (1 << letter) != 0 {
+                cnf.clauses
+                    .push(with_letter.iter().map(|i| x(*i)).collect());
+            }
+            let Some((first, rest)) = with_letter.split_first() else {
+                continue;
+            };
+            let mut seen = x(*first);
+            for i in rest {
+                cnf.clauses.push(vec![-x(*i), -seen]);
+                let next = cnf.variable();
+                cnf.define_or(next, seen, x(*i));
+                seen = next;
+            }
+        }
+        for word in options.include.iter() {
+            if let Some(i) = words.iter().position(|w| w == word) {
+                cnf.clauses.push(vec![x(i)]);
+            }
+        }
+
+        // counts[j] is r_(i,j+1) after word i, None while it must be false
+        let mut counts: Vec<Option<i64>> = vec![None; size];
+        for i in 0..words.len() {
+            if let Some(full) = counts[size - 1] {
+                cnf.clauses.push(vec![-x(i), -full]);
+            }
+            let mut next = counts.clone();
+            for j in 0..size {
+                // r_(i-1,0) is always true
+                let below = match j {
+                    0 => None,
+                    _ => match counts[j - 1] {
+                        Some(below) => Some(below),
+                        None => continue,
+                    },
+                };
+                let v = cnf.variable();
+                match (counts[j], below) {
+                    (None, None) => {
+                        cnf.clauses.push(vec![-v, x(i)]);
+                        cnf.clauses.push(vec![v, -x(i)]);
+                    }
+                    (Some(same), None) => cnf.define_or(v, same, x(i)),
+                    (None, Some(below)) => {
+                        cnf.clauses.push(vec![-v, below]);
+                        cnf.clauses.push(vec![-v, x(i)]);
+                        cnf.clauses.push(vec![v, -below, -x(i)]);
+                    }
+                    (Some(same), Some(below)) => cnf.define_or_and(v, same, below, x(i)),
+                }
+                next[j] = Some(v);
+            }
+            counts = next;
+        }
+        match counts[size - 1] {
+            Some(full) => cnf.clauses.push(vec![full]),
+            None => cnf.clauses.push(Vec::new()), // fewer than K words
+        }
+        cnf
+    }
+
+    pub fn write(
+        &self,
+        out: &mut impl Write,
+        words: &[String],
+        size: usize,
+    ) -> std::io::Result<()> {
+        writeln!(out, "c five_letter_words word packing, {} words", size)?;
+        for (i, word) in words.iter().enumerate() {
+            writeln!(out, "c word {} {}", i + 1, word)?;
+        }
+        // The other variables are defined by these, for projected model counters
+        writeln!(
+            out,
+            "c p show {} 0",
+            (1..=words.len())
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )?;
+        writeln!(out, "p cnf {} {}", self.variables, self.clauses.len())?;
+        for clause in self.clauses.iter() {
+            for literal in clause {
+                write!(out, "{} ", literal)?;
+            }
+            writeln!(out, "0")?;
+        }
+        Ok(())
+    }
+}
+
+/// `export-cnf <out> [K]`: writes the CNF for K words (5 by default) from the word list.
+pub fn export_cnf(words: &[String], args: &[String], options: &Options) {
+    let out = match args.first() {
+        Some(out) => Path::new(out),
+        None => panic!("usage: <wordfile> export-cnf <out> [K]"),
+    };
+    let size = match args.get(1).map(|size| size.parse()) {
+        None => DEFAULT_SET_SIZE,
+        Some(Ok(size)) if (1..=26).contains(&size) => size,
+        Some(_) => panic!("invalid set size {} (expected 1 to 26)", args[1]),
+    };
+    let mut seen = HashSet::new();
+    let words: Vec<String> = words
+        .iter()
+        .filter(|word| seen.insert(word.as_str()))
+        .cloned()
+        .collect();
+    let cnf = Cnf::word_packing(&words, size, options);
+    let result = fs::File::create(out).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+        cnf.write(&mut writer, &words, size)?;
+        writer.flush()
+    });
+    if let Err(why) = result {
+        panic!("couldn't write {}: {}", out.display(), why);
+    }
+    eprintln!(
+        "cnf: {} word variables, {} variables, {} clauses",
+        words.len(),
+        cnf.variables,
+        cnf.clauses.len()
+    );
+}
+
+/// Word of each variable, from the comments of a CNF written by `export_cnf`.
+fn cnf_words(text: &str) -> Vec<(i64, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let mut fields = line.strip_prefix("c word ")?.split_whitespace();
+            let variable = fields.next()?.parse().ok()?;
+            Some((variable, fields.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// The models in a solver's output: `v` lines in the competition format, or bare literals as
+/// MiniSat writes them, each model ending with 0. Comment lines (`c ...`) are skipped, and once
+/// there is a `v` line, only `v` lines are read. None if the solver says there is no model.
+pub fn parse_models(text: &str) -> Option<Vec<HashSet<i64>>> {
+    let mut models = Vec::new();
+    let mut model = HashSet::new();
+    let v_lines = text.lines().any(|line| line.trim().starts_with("v "));
+    for line in text.lines() {
+        let line = line.trim();
+        if line.starts_with("s UNSAT") || line == "UNSAT" || line == "UNSATISFIABLE" {
+            return None;
+        }
+        if line.starts_with('c') {
+            continue;
+        }
+        let literals = match line.strip_prefix("v ") {
+            Some(literals) => literals,
+            None if v_lines => continue,
+            None => line,
+        };
+        for literal in literals
+            .split_whitespace()
+            .filter_map(|l| l.parse::<i64>().ok())
+        {
+            if literal == 0 {
+                models.push(std::mem::take(&mut model));
+            } else {
+                model.insert(literal);
+            }
+        }
+    }
+    if !model.is_empty() {
+        models.push(model);
+    }
+    Some(models)
+}
+
+/// `import-model <cnf> <model>`: prints the words of each model in a solver's output for a CNF
+/// from export-cnf, checking that they are in the word list and share no letters.
+pub fn import_model(words: &[String], args: &[String]) {
+    let [cnf_path, model_path] = args else {
+        panic!("usage: <wordfile> import-model <cnf> <model>");
+    };
+    let read = |path: &String| match fs::read_to_string(path) {
+        Err(why) => panic!("couldn't read {}: {}", path, why),
+        Ok(text) => text,
+    };
+    let variables = cnf_words(&read(cnf_path));
+    let Some(models) = parse_models(&read(model_path)) else {
+        println!("unsatisfiable");
+        return;
+    };
+    let known: HashSet<&str> = words.iter().map(|word| word.as_str()).collect();
+    for model in models.iter() {
+        let chosen: Vec<&str> = variables
+            .iter()
+            .filter(|(variable, _)| model.contains(variable))
+            .map(|(_, word)| word.as_str())
+            .collect();
+        for word in chosen.iter().filter(|word| !known.contains(*word)) {
+            eprintln!("warning: {} is not in the word list", word);
+        }
+        let letters = chosen.iter().fold(0, |acc, word| acc | get_repr(word));
+        if letters.count_ones() as usize != chosen.len() * 5 {
+            eprintln!("warning: the words of this model share letters");
+        }
+        println!("{}", chosen.join(" "));
+    }
+    eprintln!("{} models", models.len());
+}
+
+/// Number of models of `cnf`, by DPLL with unit propagation.
+#[cfg(test)]
+fn count_models(cnf: &Cnf) -> usize {
+    fn count(clauses: &[Vec<i64>], assigned: &mut Vec<i64>, variables: usize) -> usize {
+        let value = |assigned: &[i64], literal: i64| {
+            assigned
+                .iter()
+                .find(|a| a.abs() == literal.abs())
+                .map(|a| *a == literal)
+        };
+        // Propagate units until nothing changes
+        let mark = assigned.len();
+        loop {
+            let mut unit = None;
+            for clause in clauses {
+                let values: Vec<Option<bool>> =
+                    clause.iter().map(|l| value(assigned, *l)).collect();
+                if values.contains(&Some(true)) {
+                    continue;
+                }
+                let open: Vec<i64> = clause
+                    .iter()
+                    .zip(values.iter())
+                    .filter(|(_, v)| v.is_none())
+                    .map(|(l, _)| *l)
+                    .collect();
+                match open.len() {
+                    0 => {
+                        assigned.truncate(mark);
+                        return 0;
+                    }
+                    1 => {
+                        unit = Some(open[0]);
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+            match unit {
+                Some(literal) => assigned.push(literal),
+                None => break,
+            }
+        }
+        let free = (1..=variables as i64).find(|v| value(assigned, *v).is_none());
+        let result = match free {
+            None => 1,
+            Some(v) => [v, -v]
+                .iter()
+                .map(|literal| {
+                    assigned.push(*literal);
+                    let n = count(clauses, assigned, variables);
+                    assigned.pop();
+                    n
+                })
+                .sum(),
+        };
+        assigned.truncate(mark);
+        result
+    }
+    count(&cnf.clauses, &mut Vec::new(), cnf.variables)
+}
+
+#[test]
+fn test_cnf_models_are_solutions() {
+    use itertools::Itertools;
+
+    let words: Vec<String> = [
+        "fjord", "gucks", "nymph", "vibex", "waltz", "glent", "jumby", "prick", "bling", "chunk",
+        "crwth",
+    ]
+    .iter()
+    .map(|w| w.to_string())
+    .collect();
+    let disjoint = |set: &Vec<&String>| {
+        set.iter()
+            .fold(0, |acc, word| acc | get_repr(word))
+            .count_ones() as usize
+            == set.len() * 5
+    };
+    for size in 1..=5 {
+        let expected = words.iter().combinations(size).filter(disjoint).count();
+        let cnf = Cnf::word_packing(&words, size, &Options::default());
+        assert_eq!(count_models(&cnf), expected, "{} words", size);
+    }
+
+    let options = Options {
+        include: vec!["waltz".to_string()],
+        require_letters: 1 << (b'y' - b'a'),
+        ..Options::default()
+    };
+    let expected = words
+        .iter()
+        .combinations(3)
+        .filter(disjoint)
+        .filter(|set| set.iter().any(|w| *w == "waltz") && set.iter().any(|w| w.contains('y')))
+        .count();
+    assert!(expected > 0);
+    assert_eq!(
+        count_models(&Cnf::word_packing(&words, 3, &options)),
+        expected
+    );
+}
+
+#[test]
+fn test_parse_models() {
+    let models = parse_models("c comment\ns SATISFIABLE\nv 1 -2 3\nv -4 0\n").unwrap();
+    assert_eq!(models, vec![HashSet::from([1, -2, 3, -4])]);
+    let models = parse_models("SAT\n-1 2 0\n1 -2 0\n").unwrap();
+    assert_eq!(models.len(), 2);
+    assert_eq!(parse_models("s UNSATISFIABLE\n"), None);
+    // Solver statistics are not literals, with or without `v` lines
+    let stats = "c conflicts: 1234\nc restarts 7 0\ns SATISFIABLE\n";
+    let models = parse_models(&format!("{}v 1 -2 0\n", stats)).unwrap();
+    assert_eq!(models, vec![HashSet::from([1, -2])]);
+    let models = parse_models(&format!("{}SAT\n1 -2 0\n", stats)).unwrap();
+    assert_eq!(models, vec![HashSet::from([1, -2])]);
+    let models = parse_models("s SATISFIABLE\n3 0\nv 1 -2 0\n").unwrap();
+    assert_eq!(models, vec![HashSet::from([1, -2])]);
+
+    let mut out = Vec::new();
+    let words = vec!["fjord".to_string(), "waltz".to_string()];
+    Cnf::word_packing(&words, 2, &Options::default())
+        .write(&mut out, &words, 2)
+        .unwrap();
+    let text = String::from_utf8(out).unwrap();
+    assert_eq!(
+        cnf_words(&text),
+        vec![(1, "fjord".to_string()), (2, "waltz".to_string())]
+    );
+}
@@ -0,0 +1,172 @@
+// For every key, a bitmap of the later keys that are letter-disjoint with it.
+//
+// With the index, the candidates for the next word of a combo are the intersection (bitwise AND)
+// of the rows of the words already in it, so no key has to be re-tested against the combo.
+// brute, brute_par and smart_brute_par search this way; brute_simd and smart_brute_simd keep
+// scanning the keys with `simd_filter`.
+
+use std::time::{Duration, Instant};
+
+use crate::simd_filter::filter_disjoint_positions;
+
+pub struct CompatIndex {
+    keys_count: usize,
+    words_per_row: usize,
+    // Row for position i is bits[i * words_per_row..(i + 1) * words_per_row];
+    // bit j is set iff j > i and keys[i] & keys[j] == 0
+    bits: Vec<u64>,
+    pair_count: usize,
+    build_time: Duration,
+}
+
+impl CompatIndex {
+    pub fn build(keys: &[u32]) -> Self {
+        let start = Instant::now();
+        let words_per_row = keys.len().div_ceil(64);
+        let mut bits = vec![0u64; keys.len() * words_per_row];
+        let mut pair_count = 0;
+        let mut positions = Vec::new();
+        for (pos, key) in keys.iter().enumerate() {
+            positions.clear();
+            filter_disjoint_positions(&keys[pos + 1..], *key, &mut positions);
+            let row = &mut bits[pos * words_per_row..(pos + 1) * words_per_row];
+            for offset in positions.iter() {
+                let later = pos + 1 + *offset as usize;
+                row[later / 64] |= 1 << (later % 64);
+            }
+            pair_count += positions.len();
+        }
+        CompatIndex {
+            keys_count: keys.len(),
+            words_per_row,
+            bits,
+            pair_count,
+            build_time: start.elapsed(),
+        }
+    }
+
+    /// Bitmap of the positions after `pos` whose keys are disjoint with the key at `pos`.
+    #[inline]
+    pub fn row(&self, pos: usize) -> &[u64] {
+        &self.bits[pos * self.words_per_row..(pos + 1) * self.words_per_row]
+    }
+
+    /// Bitmap of every position
+    pub fn all(&self) -> Vec<u64> {
+        let mut bits = vec![0u64; self.words_per_row];
+        for pos in 0..self.keys_count {
+            bits[pos / 64] |= 1 << (pos % 64);
+        }
+        bits
+    }
+
+    /// Positions after `pos` whose keys are disjoint with the key at `pos`, ascending.
+    pub fn later(&self, pos: usize) -> impl Iterator<Item = usize> + '_ {
+        set_bits(self.row(pos), pos / 64)
+    }
+
+    pub fn words_per_row(&self) -> usize {
+        self.words_per_row
+    }
+
+    /// Number of disjoint pairs stored.
+    pub fn pair_count(&self) -> usize {
+        self.pair_count
+    }
+
+    pub fn memory_bytes(&self) -> usize {
+        self.bits.capacity() * std::mem::size_of::<u64>()
+    }
+
+    pub fn report(&self) {
+        eprintln!(
+            "compat index: {} keys, {} disjoint pairs, {:.2} MiB, built in {:.1} ms",
+            self.keys_count,
+            self.pair_count,
+            self.memory_bytes() as f64 / (1024.0 * 1024.0),
+            self.build_time.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+/// Positions of the set bits in `bitmap`, starting the scan at word `first_word`.
+pub fn set_bits(bitmap: &[u64], first_word: usize) -> impl Iterator<Item = usize> + '_ {
+    bitmap
+        .iter()
+        .enumerate()
+        .skip(first_word)
+        .flat_map(|(word_no, word)| {
+            let mut word = *word;
+            std::iter::from_fn(move || {
+                if word == 0 {
+                    return None;
+                }
+                let bit = word.trailing_zeros() as usize;
+                word &= word - 1;
+                Some(word_no * 64 + bit)
+            })
+        })
+}
+
+/// out = a & b, for words from `first_word` on (earlier words of `out` are left as they were).
+#[inline]
+pub fn intersect_rows(a: &[u64], b: &[u64], first_word: usize, out: &mut [u64]) {
+    for ((o, x), y) in out[first_word..]
+        .iter_mut()
+        .zip(a[first_word..].iter())
+        .zip(b[first_word..].iter())
+    {
+        *o = x & y;
+    }
+}
+
+#[test]
+fn test_compat_index() {
+    let keys = vec![0b0011, 0b0100, 0b0110, 0b1000, 0b1001];
+    let index = CompatIndex::build(&keys);
+    assert_eq!(index.later(0).collect::<Vec<_>>(), vec![1, 3]);
+    assert_eq!(index.later(1).collect::<Vec<_>>(), vec![3, 4]);
+    assert_eq!(index.later(2).collect::<Vec<_>>(), vec![3, 4]);
+    assert_eq!(index.later(3).count(), 0);
+    assert_eq!(index.later(4).count(), 0);
+    assert_eq!(index.pair_count(), 6);
+
+    let mut common = vec![0; index.words_per_row()];
+    intersect_rows(index.row(1), index.row(2), 0, &mut common);
+    assert_eq!(set_bits(&common, 0).collect::<Vec<_>>(), vec![3, 4]);
+}
+
+#[test]
+fn test_compat_index_multi_word_rows() {
+    let keys: Vec<u32> = (0..150).map(|i| 1 << (i % 26)).collect();
+    let index = CompatIndex::build(&keys);
+    assert_eq!(index.words_per_row(), 3);
+    assert!(set_bits(&index.all(), 0).eq(0..keys.len()));
+    for pos in [0, 63, 64, 100, 149] {
+        let expected: Vec<usize> = (pos + 1..keys.len())
+            .filter(|later| keys[pos] & keys[*later] == 0)
+            .collect();
+        assert_eq!(index.later(pos).collect::<Vec<_>>(), expected);
+    }
+}
+
+#[test]
+fn test_indexed_solvers_match_scanning() {
+    use crate::word_reprs::*;
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+
+    let expected = collect_sorted(
+        crate::backtracking_brute::backtracking_brute_simd_keys,
+        &keys,
+    );
+    assert!(!expected.is_empty());
+    assert_eq!(
+        collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys),
+        expected
+    );
+    assert_eq!(
+        collect_sorted(crate::smart_brute::smart_brute_keys, &keys),
+        expected
+    );
+}
@@ -0,0 +1,298 @@
+// --include, --exclude, --require-letters and --unused-letter.
+//
+// The word filters run on the word list before anything else: excluded words are dropped, and
+// an included word's anagram class is narrowed down to that word. The rest is pushed into the
+// solvers through `Constraints`: the included words' keys seed the combo (or are pre-selected
+// in DLX), only keys disjoint with them and free of the unused letter stay candidates, and the
+// required letters restrict which letter a combo may leave out. The backtracking searches drop
+// a branch as soon as its remaining candidates can't add the required letters it still misses.
+//
+// Solvers that can't be seeded get the included keys among their candidates instead. A combo
+// of five disjoint candidates that skips an included key would have to fit 25 letters into the
+// at most 21 that the other included keys and the free keys have between them, so every combo
+// they find contains all included keys.
+
+use std::collections::HashSet;
+
+use itertools::Itertools;
+
+use crate::options::Options;
+use crate::word_reprs::{get_repr, ComboSink};
+
+const ALL_LETTERS: u32 = (1 << 26) - 1;
+
+/// Drops the excluded words and the anagrams of the included ones. Panics if an included word
+/// is not five different lowercase letters, an anagram of another included word, or not in the
+/// list.
+pub fn filter_words(words: Vec<String>, options: &Options) -> Vec<String> {
+    match try_filter_words(words, options) {
+        Err(why) => panic!("{}", why),
+        Ok(words) => words,
+    }
+}
+
+/// Same as `filter_words`, returning the bad included word as an error instead of panicking.
+pub fn try_filter_words(words: Vec<String>, options: &Options) -> Result<Vec<String>, String> {
+    if options.include.is_empty() && options.exclude.is_empty() {
+        return Ok(words);
+    }
+    for word in options.include.iter() {
+        if !is_valid_include(word) {
+            return Err(format!(
+                "included word {} must be 5 different lowercase letters",
+                word
+            ));
+        }
+    }
+    // One word per anagram class makes it into the list, so two of them can't both be included
+    for (a, b) in options.include.iter().tuple_combinations() {
+        if a != b && get_repr(a) == get_repr(b) {
+            return Err(format!(
+                "included words {} and {} are anagrams, so no solution has both",
+                a, b
+            ));
+        }
+    }
+    let excluded: HashSet<&str> = options.exclude.iter().map(|w| w.as_str()).collect();
+    let included_reprs: HashSet<u32> = options.include.iter().map(|w| get_repr(w)).collect();
+    let words: Vec<String> = words
+        .into_iter()
+        .filter(|word| !excluded.contains(word.as_str()))
+        .filter(|word| !included_reprs.contains(&get_repr(word)) || options.include.contains(word))
+        .collect();
+    for word in options.include.iter() {
+        if !words.contains(word) {
+            return Err(format!("included word {} is not in the word list", word));
+        }
+    }
+    Ok(words)
+}
+
+/// Whether `word` can be part of a solution at all; `get_repr` only takes lowercase letters.
+fn is_valid_include(word: &str) -> bool {
+    word.len() == 5
+        && word.bytes().all(|byte| byte.is_ascii_lowercase())
+        && get_repr(word).count_ones() == 5
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Constraints {
+    /// Keys of the included words, which every combo contains
+    pub required: Vec<u32>,
+    /// Letters of the included words
+    pub required_repr: u32,
+    /// Letters every combo must use
+    pub letters: u32,
+    /// Letters no combo may use
+    pub banned: u32,
+}
+
+impl Constraints {
+    pub fn new(options: &Options) -> Self {
+        let mut required: Vec<u32> = options.include.iter().map(|w| get_repr(w)).collect();
+        // Including a word twice asks for nothing more
+        required.sort();
+        required.dedup();
+        let banned = options.unused_letter.map_or(0, |letter| 1 << letter);
+        let mut letters = options.require_letters;
+        if banned != 0 {
+            letters |= ALL_LETTERS & !banned;
+        }
+        Constraints {
+            required_repr: required.iter().fold(0, |acc, key| acc | key),
+            required,
+            letters,
+            banned,
+        }
+    }
+
+    /// Whether any combo can satisfy the constraints at all
+    pub fn feasible(&self) -> bool {
+        let disjoint = self
+            .required
+            .iter()
+            .map(|key| key.count_ones())
+            .sum::<u32>()
+            == self.required_repr.count_ones();
+        disjoint
+            && self.required.len() <= 5
+            && self.required_repr & self.banned == 0
+            && self.letters & self.banned == 0
+    }
+
+    /// Letters a combo may leave out
+    pub fn skippable(&self) -> u32 {
+        ALL_LETTERS & !self.letters
+    }
+
+    /// Keys that can join the included ones, in the order of `keys`
+    pub fn free_keys(&self, keys: &[u32]) -> Vec<u32> {
+        let taken = self.required_repr | self.banned;
+        keys.iter()
+            .copied()
+            .filter(|key| key & taken == 0)
+            .collect()
+    }
+
+    /// The free keys and the included ones, sorted; see the module comment
+    pub fn candidate_keys(&self, keys: &[u32]) -> Vec<u32> {
+        let mut candidates = self.free_keys(keys);
+        candidates.extend(self.required.iter());
+        candidates.sort();
+        candidates
+    }
+
+    /// Combo, depth and combo_repr to start a backtracking search from
+    pub fn seed(&self) -> ([u32; 5], usize, u32) {
+        let mut combo = [0; 5];
+        combo[..self.required.len()].copy_from_slice(&self.required);
+        (combo, self.required.len(), self.required_repr)
+    }
+
+    pub fn accepts(&self, combo: &[u32; 5]) -> bool {
+        let combo_repr = combo.iter().fold(0, |acc, key| acc | key);
+        combo_repr & self.letters == self.letters
+    }
+
+    /// Whether a combo using the letters of `combo_repr` can still use every required letter
+    /// when the rest of its words come from `keys`
+    pub fn can_complete(&self, combo_repr: u32, keys: impl IntoIterator<Item = u32>) -> bool {
+        let missing = self.letters & !combo_repr;
+        missing == 0 || missing & !keys.into_iter().fold(0, |acc, key| acc | key) == 0
+    }
+
+    /// `Reach` over `keys`, for searches that take the next words from a position on
+    pub fn reach(&self, keys: &[u32]) -> Reach {
+        let mut suffix = vec![0; keys.len() + 1];
+        for pos in (0..keys.len()).rev() {
+            suffix[pos] = suffix[pos + 1] | keys[pos];
+        }
+        Reach {
+            letters: self.letters,
+            suffix,
+        }
+    }
+
+    /// `emit`, checking in debug builds that the solver pruned every combo missing a required
+    /// letter
+    pub fn sink<'a>(&'a self, emit: &'a ComboSink) -> impl Fn([u32; 5]) + Sync + 'a {
+        move |combo| {
+            debug_assert!(self.accepts(&combo), "{:?} misses a required letter", combo);
+            emit(combo)
+        }
+    }
+}
+
+/// The letters the keys from each position on can still add to a combo.
+pub struct Reach {
+    letters: u32,
+    suffix: Vec<u32>,
+}
+
+impl Reach {
+    /// Whether a combo of `depth` words using the letters of `combo_repr` can still use every
+    /// required letter when the rest of its words come from position `pos` on
+    #[inline]
+    pub fn allows(&self, combo_repr: u32, depth: usize, pos: usize) -> bool {
+        let reachable = if depth == 5 { 0 } else { self.suffix[pos] };
+        self.letters & !(combo_repr | reachable) == 0
+    }
+}
+
+/// Parses the letters of --require-letters or --unused-letter into a bitmask.
+pub fn parse_letters(value: &str) -> Option<u32> {
+    value.bytes().try_fold(0, |acc, letter| {
+        if letter.is_ascii_lowercase() {
+            Some(acc | 1 << (letter - b'a'))
+        } else {
+            None
+        }
+    })
+}
+
+#[test]
+fn test_constraints_match_filtered_output() {
+    use crate::word_reprs::*;
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let all = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    let letter = |l: u8| 1u32 << (l - b'a');
+
+    let cases = [
+        Options {
+            include: vec![String::from("fjord")],
+            ..Options::default()
+        },
+        Options {
+            include: vec![String::from("fjord"), String::from("waltz")],
+            require_letters: letter(b'x'),
+            ..Options::default()
+        },
+        Options {
+            unused_letter: Some((b'q' - b'a') as u32),
+            ..Options::default()
+        },
+        Options {
+            require_letters: letter(b'q') | letter(b'v'),
+            ..Options::default()
+        },
+    ];
+    for options in cases.iter() {
+        let constraints = Constraints::new(options);
+        let expected: Vec<[u32; 5]> = all
+            .iter()
+            .filter(|combo| {
+                constraints.required.iter().all(|key| combo.contains(key))
+                    && combo.iter().all(|key| key & constraints.banned == 0)
+                    && constraints.accepts(combo)
+            })
+            .copied()
+            .collect();
+        assert!(!expected.is_empty() && expected.len() < all.len());
+        for (name, solver) in crate::SOLVERS {
+            assert_eq!(
+                collect_sorted_with(*solver, &keys, options),
+                expected,
+                "{} with {:?}",
+                name,
+                options
+            );
+        }
+    }
+}
+
+#[test]
+fn test_filter_words() {
+    let words: Vec<String> = ["angle", "glean", "fjord", "waltz"]
+        .iter()
+        .map(|w| w.to_string())
+        .collect();
+    let options = Options {
+        include: vec![String::from("glean")],
+        exclude: vec![String::from("waltz")],
+        ..Options::default()
+    };
+    assert_eq!(
+        filter_words(words.clone(), &options),
+        vec!["glean", "fjord"]
+    );
+
+    for include in ["Fjord", "fjor", "fj0rd", "xylyl"] {
+        let options = Options {
+            include: vec![include.to_string()],
+            ..Options::default()
+        };
+        assert!(try_filter_words(words.clone(), &options).is_err());
+    }
+    let options = Options {
+        include: vec![String::from("fjord"), String::from("fjord")],
+        ..Options::default()
+    };
+    assert_eq!(filter_words(words.clone(), &options).len(), 4);
+    assert!(Constraints::new(&options).feasible());
+    let options = Options {
+        include: vec![String::from("angle"), String::from("glean")],
+        ..Options::default()
+    };
+    assert!(try_filter_words(words, &options).is_err());
+}
@@ -1,8 +1,11 @@
 // DL = Dancing Links
 
+#[cfg(test)]
 use prettytable::{Cell, Row, Table};
 
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+
+use crate::exact_cover::ExactCover;
 
 type DLPtr = usize;
 
@@ -37,11 +40,13 @@ pub enum DLNode {
     Column {
         nav: DLNav,
         size: usize,
+        #[cfg_attr(not(test), allow(dead_code))] // only read when printing the matrix
         x: usize,
     },
     Cell {
         nav: DLNav,
         column: DLPtr,
+        #[cfg_attr(not(test), allow(dead_code))]
         x: usize,
         y: usize,
     },
@@ -56,7 +61,7 @@ impl DLNode {
         }
     }
 
-    fn get_nav_mut<'a>(&'a mut self) -> &'a mut DLNav {
+    fn get_nav_mut(&mut self) -> &mut DLNav {
         match self {
             DLNode::Root { nav } => nav,
             DLNode::Column { nav, .. } => nav,
@@ -132,7 +137,7 @@ impl DLMatrix {
     }
 
     fn root(&self) -> &DLNode {
-        self.arena.get(0).unwrap()
+        self.arena.first().unwrap()
     }
 
     fn root_ptr(&self) -> DLPtr {
@@ -224,7 +229,8 @@ impl DLMatrix {
     }
 
     /// Prefer from_bool_rows as more performant
-    fn from_bool_columns(columns: &Vec<Vec<bool>>) -> Self {
+    #[cfg(test)]
+    fn from_bool_columns(columns: &[Vec<bool>]) -> Self {
         let mut res = Self::new();
         for (x, column) in columns.iter().enumerate() {
             for (y, value) in column.iter().enumerate() {
@@ -236,7 +242,7 @@ impl DLMatrix {
         res
     }
 
-    fn from_bool_rows(rows: &Vec<Vec<bool>>) -> Self {
+    fn from_bool_rows(rows: &[Vec<bool>]) -> Self {
         let mut res = Self::new();
         for (y, row) in rows.iter().enumerate() {
             for (x, value) in row.iter().enumerate() {
@@ -273,6 +279,7 @@ impl DLMatrix {
         }
     }
 
+    #[cfg(debug_assertions)]
     fn node_sanity_check(&self, ptr: DLPtr) {
         if self
             .get_neigh_ptr(self.get_neigh_ptr(ptr, Dir::Left).unwrap(), Dir::Right)
@@ -305,6 +312,7 @@ impl DLMatrix {
         }
     }
 
+    #[cfg(debug_assertions)]
     fn column_sanity_check(&self, col_ptr: DLPtr) {
         let mut j = col_ptr;
         let mut ctr = 0;
@@ -352,7 +360,7 @@ impl DLMatrix {
         let col = self.get_column_ptr(ptr);
         if col != ptr {
             match self.get_node_mut(col).unwrap() {
-                DLNode::Column { nav, size, x } => {
+                DLNode::Column { size, .. } => {
                     *size -= 1;
                 }
                 _ => unreachable!(),
@@ -369,7 +377,7 @@ impl DLMatrix {
         let col = self.get_column_ptr(ptr);
         if col != ptr {
             match self.get_node_mut(col).unwrap() {
-                DLNode::Column { nav, size, x } => {
+                DLNode::Column { size, .. } => {
                     *size += 1;
                 }
                 _ => unreachable!(),
@@ -381,13 +389,12 @@ impl DLMatrix {
     pub fn exact_cover(&mut self) -> Vec<Vec<usize>> {
         let mut o_vals: Vec<DLPtr> = Vec::new();
         let mut solutions: Vec<Vec<usize>> = Vec::new();
-        self.exact_cover_rec(0, &mut o_vals, &mut solutions);
+        self.exact_cover_rec(&mut o_vals, &mut solutions);
         solutions
     }
 
     fn exact_cover_rec(
         &mut self,
-        k: usize,
         partial_solution: &mut Vec<DLPtr>,
         solutions: &mut Vec<Vec<usize>>,
     ) {
@@ -423,7 +430,7 @@ impl DLMatrix {
                 }
             }
 
-            self.exact_cover_rec(k + 1, partial_solution, solutions);
+            self.exact_cover_rec(partial_solution, solutions);
 
             // Undo covering the columns
             loop {
@@ -490,7 +497,7 @@ impl DLMatrix {
         self.relink_left_right(col_ptr);
     }
 
-    fn current_solution(&mut self, partial_solution: &mut Vec<DLPtr>) -> Vec<usize> {
+    fn current_solution(&mut self, partial_solution: &[DLPtr]) -> Vec<usize> {
         let mut res: Vec<usize> = Vec::new();
         for &ptr in partial_solution.iter() {
             res.push(match self.get_node(ptr).unwrap() {
@@ -506,6 +513,7 @@ impl DLMatrix {
         res
     }
 
+    #[cfg(test)]
     fn print(&self) {
         let root_ptr = self.root_ptr();
         let mut columns = HashMap::new();
@@ -582,206 +590,22 @@ impl DLMatrix {
     }
 }
 
-#[test]
-fn test_create_dlmatrix() {
-    let columns = vec![
-        vec![true, false, true],
-        vec![false, true, false],
-        vec![true, true, true],
-    ];
-    let m = DLMatrix::from_bool_columns(&columns);
-    dbg!(m);
-    // TODO: add a way to traverse the matrix
-}
-
-#[test]
-fn test_simple_exact_cover() {
-    let columns = vec![
-        vec![true, false, true],
-        vec![true, true, false],
-        vec![false, true, false],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 1);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        dbg!(&solution);
-        println!("");
+impl ExactCover for DLMatrix {
+    fn from_bool_rows(rows: &[Vec<bool>]) -> Self {
+        DLMatrix::from_bool_rows(rows)
     }
-}
 
-#[test]
-fn test_simple_exact_cover_3el_col() {
-    let columns = vec![
-        vec![true, true, true],
-        vec![false, true, false],
-        vec![false, true, true],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 1);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        dbg!(&solution);
-        println!("");
+    #[cfg(test)]
+    fn from_bool_columns(columns: &[Vec<bool>]) -> Self {
+        DLMatrix::from_bool_columns(columns)
     }
-}
 
-#[test]
-fn test_simple_exact_cover_multi_solution() {
-    let columns = vec![
-        vec![true, false, true],
-        vec![true, false, true],
-        vec![false, true, true],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 2);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        dbg!(&solution);
-        println!("");
+    fn solutions(&mut self) -> Vec<Vec<usize>> {
+        self.exact_cover()
     }
-}
 
-#[test]
-fn test_medium_exact_cover() {
-    let columns = vec![
-        vec![false, true, false, true, false, false],
-        vec![false, false, true, false, true, false],
-        vec![true, false, true, false, false, false],
-        vec![false, true, false, true, false, true],
-        vec![true, false, false, false, false, true],
-        vec![true, false, true, false, false, false],
-        vec![false, true, false, false, false, true],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 1);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        for row in solution.iter() {
-            print!("{}", *row);
-        }
-        println!("");
-    }
-}
-
-#[test]
-fn test_weird_exact_cover() {
-    let columns = vec![
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    m.print();
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-}
-
-fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>> {
-    assert!(!v.is_empty());
-    let len = v[0].len();
-    let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
-    (0..len)
-        .map(|_| {
-            iters
-                .iter_mut()
-                .map(|n| n.next().unwrap())
-                .collect::<Vec<T>>()
-        })
-        .collect()
-}
-
-pub fn dlx_words(words: Vec<String>) {
-    let all_columns: Vec<Vec<bool>> = (b'a'..=b'z')
-        .map(|b| {
-            let ch = b as char;
-            words.iter().map(|w| w.contains(ch)).collect::<Vec<bool>>()
-        })
-        .collect();
-
-    let dummy_row = vec![true; 26 - 1];
-
-    let mut ctr = 0;
-    for left_out in 0..26 {
-        eprintln!("{}", (left_out + b'a') as char);
-
-        // Remove column corresponding to the left out letter
-        let columns: Vec<Vec<bool>> = all_columns
-            .clone()
-            .into_iter()
-            .enumerate()
-            .filter(|(i, _)| *i != left_out.into())
-            .map(|(_i, c)| c)
-            .collect();
-
-        // Remove words containing the left out letter
-        let left_out_column: &Vec<bool> = all_columns.get(left_out as usize).unwrap();
-        let left_out_rows: HashSet<usize> = left_out_column
-            .iter()
-            .enumerate()
-            .filter(|(_i, b)| **b)
-            .map(|(i, _b)| i)
-            .collect();
-        let mut rows: Vec<Vec<bool>> = transpose(columns)
-            .into_iter()
-            .enumerate()
-            .filter(|(i, _r)| !left_out_rows.contains(i))
-            .map(|(_i, r)| r)
-            .collect();
-
-        // Ensure every column will be created in the matrix
-        rows.push(dummy_row.clone());
-
-        // Construct the matrix and run exact cover
-        let mut dlm = DLMatrix::from_bool_rows(&rows);
-        let solutions = dlm.exact_cover();
-        for solution in solutions {
-            if solution.len() == 1 && solution[0] == rows.len() - 1 {
-                // This is the dummy row
-                continue;
-            }
-            for index in solution {
-                print!("{} ", words[index].as_str());
-            }
-            println!("");
-            ctr += 1;
-        }
+    #[cfg(test)]
+    fn print(&self) {
+        DLMatrix::print(self)
     }
-    dbg!(ctr);
 }
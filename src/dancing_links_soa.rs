@@ -1,12 +1,12 @@
 // DL = Dancing Links
 // SoA = implemented using Struct-of-Arrays approach
 
-use itertools::Itertools;
+#[cfg(test)]
 use prettytable::{Cell, Row, Table};
-use std::collections::HashMap;
-use std::io::prelude::*;
+use std::collections::{HashMap, HashSet};
 
-use crate::word_reprs::build_word_representations;
+use crate::exact_cover::{ColumnHeuristic, ExactCover};
+use crate::rng::SplitMix64;
 
 enum Dir {
     Up,
@@ -15,17 +15,92 @@ enum Dir {
     Left,
 }
 
+/// Called by `exact_cover_each` with each solution as it's found; returning false stops the search
+pub type SolutionVisitor<'a> = dyn FnMut(Vec<u32>) -> bool + 'a;
+
+/// Called by `exact_cover_resumable` with each node's path and the solutions found before it
+pub type NodeVisitor<'a> = dyn FnMut(&[usize], &[Vec<u32>]) + 'a;
+
+pub struct RandomWalk {
+    /// The rows' y coordinates if the walk ended in a solution
+    pub solution: Option<Vec<u32>>,
+    /// Number of children of each node on the path, the last one 0 for a dead end
+    pub degrees: Vec<usize>,
+}
+
+// Primary columns grouped by size, so the smallest one is found without scanning them all
+#[derive(Debug)]
+struct SizeBuckets {
+    first: Vec<u32>, // first column with the given size; 0 (the root) if none
+    next: Vec<u32>,  // per node, only meaningful for columns in a bucket
+    prev: Vec<u32>,
+    primary: Vec<bool>, // column takes part in the buckets while it's linked to the root
+    member: Vec<bool>,  // column is in a bucket right now
+    min_size: usize,    // no bucket below this size has a column
+}
+
+impl SizeBuckets {
+    fn insert(&mut self, col: u32, size: usize) {
+        let next = self.first[size];
+        self.next[col as usize] = next;
+        self.prev[col as usize] = 0;
+        if next != 0 {
+            self.prev[next as usize] = col;
+        }
+        self.first[size] = col;
+        self.member[col as usize] = true;
+        self.min_size = self.min_size.min(size);
+    }
+
+    fn remove(&mut self, col: u32, size: usize) {
+        let (prev, next) = (self.prev[col as usize], self.next[col as usize]);
+        if prev != 0 {
+            self.next[prev as usize] = next;
+        } else {
+            self.first[size] = next;
+        }
+        if next != 0 {
+            self.prev[next as usize] = prev;
+        }
+        self.member[col as usize] = false;
+    }
+
+    #[inline]
+    fn resize(&mut self, col: u32, old_size: usize, new_size: usize) {
+        if self.member[col as usize] {
+            self.remove(col, old_size);
+            self.insert(col, new_size);
+        }
+    }
+
+    // Sizes only move by one at a time, so this skips few empty buckets; there must be a column
+    fn min(&mut self) -> u32 {
+        while self.first[self.min_size] == 0 {
+            self.min_size += 1;
+        }
+        self.first[self.min_size]
+    }
+}
+
 #[derive(Debug)]
 pub struct DLMatrix {
-    right: Vec<u16>,
-    left: Vec<u16>,
-    up: Vec<u16>,
-    down: Vec<u16>,
-    column: Vec<u16>,
-    y: Vec<i16>, // used as size if the node is column header, i.e. when the value is < 0
-    columns: HashMap<u16, u16>, // column node for given x
-    reverse_columns: HashMap<u16, u16>, // column x for given node pointer
-    rows: HashMap<u16, u16>, // first cell for given y
+    right: Vec<u32>,
+    left: Vec<u32>,
+    up: Vec<u32>,
+    down: Vec<u32>,
+    column: Vec<u32>,
+    y: Vec<i32>, // used as size if the node is column header, i.e. when the value is < 0
+    color: Vec<i32>, // color of a secondary column cell; 0 = none, < 0 = purified (already agrees)
+    bounds: Vec<(u16, u16)>, // [min, max] rows per column header; only set up by from_multiplicity_rows
+    taken: Vec<u16>,         // rows chosen so far per column header, alongside bounds
+    heuristic: ColumnHeuristic,
+    rng: SplitMix64,
+    buckets: Option<SizeBuckets>,  // only for ColumnHeuristic::Buckets
+    shuffle_rows: bool,            // set while sample runs
+    shard: Option<(usize, usize)>, // (index, count): only search the top-level rows i with i % count == index
+    columns: HashMap<u32, u32>,    // column node for given x
+    reverse_columns: HashMap<u32, u32>, // column x for given node pointer
+    rows: HashMap<u32, u32>,       // first cell for given y
 }
 
 impl DLMatrix {
@@ -40,10 +115,18 @@ impl DLMatrix {
             down: vec![0],
             column: vec![0],
             y: vec![0],
+            color: vec![0],
+            bounds: Vec::new(),
+            taken: Vec::new(),
+            heuristic: ColumnHeuristic::default(),
+            rng: SplitMix64::new(0),
+            buckets: None,
+            shuffle_rows: false,
+            shard: None,
         }
     }
 
-    fn set(&mut self, src: u16, direction: Dir, dst: u16) {
+    fn set(&mut self, src: u32, direction: Dir, dst: u32) {
         match direction {
             Dir::Up => self.up[src as usize] = dst,
             Dir::Right => self.right[src as usize] = dst,
@@ -53,7 +136,7 @@ impl DLMatrix {
     }
 
     #[inline]
-    fn get_neigh_ptr(&self, ptr: u16, direction: Dir) -> u16 {
+    fn get_neigh_ptr(&self, ptr: u32, direction: Dir) -> u32 {
         match direction {
             Dir::Up => self.up[ptr as usize],
             Dir::Right => self.right[ptr as usize],
@@ -63,21 +146,24 @@ impl DLMatrix {
     }
 
     #[inline]
-    fn get_column_ptr(&self, ptr: u16) -> u16 {
+    fn get_column_ptr(&self, ptr: u32) -> u32 {
         self.column[ptr as usize]
     }
 
     #[inline(always)]
-    fn root_ptr(&self) -> u16 {
+    fn root_ptr(&self) -> u32 {
         0
     }
 
     // new_node_factory(ptr) must return a DLNode struct that has valid pointers
-    fn add_node<F>(&mut self, f: F) -> u16
+    fn add_node<F>(&mut self, f: F) -> u32
     where
-        F: Fn(u16) -> (u16, u16, u16, u16, u16, i16),
+        F: Fn(u32) -> (u32, u32, u32, u32, u32, i32),
     {
-        let ptr = self.y.len() as u16;
+        let ptr: u32 = match self.y.len().try_into() {
+            Ok(ptr) => ptr,
+            Err(_) => panic!("DLMatrix is limited to {} nodes", u32::MAX),
+        };
         let (left, up, right, down, column, y) = f(ptr);
         self.left.push(left);
         self.up.push(up);
@@ -85,6 +171,7 @@ impl DLMatrix {
         self.down.push(down);
         self.y.push(y);
         self.column.push(column);
+        self.color.push(0);
 
         self.set(self.get_neigh_ptr(ptr, Dir::Left), Dir::Right, ptr);
         self.set(self.get_neigh_ptr(ptr, Dir::Up), Dir::Down, ptr);
@@ -94,7 +181,7 @@ impl DLMatrix {
         ptr
     }
 
-    fn add_column(&mut self, x: u16) -> u16 {
+    fn add_column(&mut self, x: u32) -> u32 {
         if self.columns.contains_key(&x) {
             return *self.columns.get(&x).unwrap();
         }
@@ -108,7 +195,19 @@ impl DLMatrix {
         ptr
     }
 
-    fn add_cell(&mut self, x: u16, y: u16) -> u16 {
+    // Secondary columns are not linked into the root's list: they are never chosen for branching
+    // and need not be covered, but rows still conflict on them unless their colors agree.
+    fn add_secondary_column(&mut self, x: u32) -> u32 {
+        if let Some(ptr) = self.columns.get(&x) {
+            return *ptr;
+        }
+        let ptr = self.add_node(|ptr| (ptr, ptr, ptr, ptr, ptr, -1));
+        self.columns.insert(x, ptr);
+        self.reverse_columns.insert(ptr, x);
+        ptr
+    }
+
+    fn add_cell(&mut self, x: u32, y: u32) -> u32 {
         let col_ptr = if !self.columns.contains_key(&x) {
             self.add_column(x)
         } else {
@@ -144,30 +243,146 @@ impl DLMatrix {
     }
 
     /// Prefer from_bool_rows as more performant
-    fn from_bool_columns(columns: &Vec<Vec<bool>>) -> Self {
+    #[cfg(test)]
+    fn from_bool_columns(columns: &[Vec<bool>]) -> Self {
         let mut res = Self::new();
         for (x, column) in columns.iter().enumerate() {
             for (y, value) in column.iter().enumerate() {
                 if *value {
-                    res.add_cell(x as u16, y as u16);
+                    res.add_cell(x as u32, y as u32);
                 }
             }
         }
         res
     }
 
-    fn from_bool_rows(rows: &Vec<Vec<bool>>) -> Self {
+    fn from_bool_rows(rows: &[Vec<bool>]) -> Self {
         let mut res = Self::new();
         for (y, row) in rows.iter().enumerate() {
             for (x, value) in row.iter().enumerate() {
                 if *value {
-                    res.add_cell(x as u16, y as u16);
+                    res.add_cell(x as u32, y as u32);
                 }
             }
         }
         res
     }
 
+    /// Matrix for exact cover with colors (Knuth's Algorithm C). Columns 0..primary_columns must
+    /// be covered exactly once, the others at most once, except that any number of rows may share
+    /// a secondary column if they give it the same color. Each row lists its (x, color) cells;
+    /// color 0 marks an uncolored cell, and primary cells must be uncolored.
+    pub fn from_colored_rows(primary_columns: u32, rows: &[Vec<(u32, i32)>]) -> Self {
+        let mut res = Self::new();
+        for x in 0..primary_columns {
+            res.add_column(x);
+        }
+        for (y, row) in rows.iter().enumerate() {
+            for &(x, color) in row.iter() {
+                if x < primary_columns {
+                    assert!(color == 0, "primary column {} can't have a color", x);
+                } else {
+                    assert!(color >= 0, "negative color {} in column {}", color, x);
+                    res.add_secondary_column(x);
+                }
+                let ptr = res.add_cell(x, y as u32);
+                res.color[ptr as usize] = color;
+            }
+        }
+        res
+    }
+
+    /// Matrix for exact cover with multiplicities (Knuth's Algorithm M): column x must be hit by
+    /// between bounds[x].0 and bounds[x].1 of the chosen rows. Each row lists its columns.
+    pub fn from_multiplicity_rows(bounds: &[(u16, u16)], rows: &[Vec<u32>]) -> Self {
+        let mut res = Self::new();
+        for x in 0..bounds.len() {
+            res.add_column(x as u32);
+        }
+        for (y, row) in rows.iter().enumerate() {
+            for x in row.iter() {
+                assert!((*x as usize) < bounds.len(), "column {} has no bounds", x);
+                res.add_cell(*x, y as u32);
+            }
+        }
+        res.bounds = vec![(1, 1); res.y.len()];
+        res.taken = vec![0; res.y.len()];
+        for (x, (min, max)) in bounds.iter().enumerate() {
+            assert!(min <= max, "column {} has min {} > max {}", x, min, max);
+            res.bounds[res.columns[&(x as u32)] as usize] = (*min, *max);
+        }
+        res
+    }
+
+    /// Picks how the exact cover searches choose the column to branch on. Static sorts the
+    /// primary columns by their current size once.
+    pub fn set_heuristic(&mut self, heuristic: ColumnHeuristic) {
+        self.heuristic = heuristic;
+        self.buckets = None;
+        let mut primary = Vec::new();
+        let mut j = self.get_neigh_ptr(self.root_ptr(), Dir::Right);
+        while j != self.root_ptr() {
+            primary.push(j);
+            j = self.get_neigh_ptr(j, Dir::Right);
+        }
+        match heuristic {
+            ColumnHeuristic::Static => {
+                primary.sort_by_key(|col| self.size(*col));
+                let mut left = self.root_ptr();
+                for col in primary.into_iter().chain([self.root_ptr()]) {
+                    self.set(left, Dir::Right, col);
+                    self.set(col, Dir::Left, left);
+                    left = col;
+                }
+            }
+            ColumnHeuristic::Buckets => {
+                let max_size = primary.iter().map(|col| self.size(*col)).max();
+                let mut buckets = SizeBuckets {
+                    first: vec![0; max_size.unwrap_or(0) + 1],
+                    next: vec![0; self.y.len()],
+                    prev: vec![0; self.y.len()],
+                    primary: vec![false; self.y.len()],
+                    member: vec![false; self.y.len()],
+                    min_size: 0,
+                };
+                for col in primary {
+                    buckets.primary[col as usize] = true;
+                    buckets.insert(col, self.size(col));
+                }
+                self.buckets = Some(buckets);
+            }
+            ColumnHeuristic::Mrv | ColumnHeuristic::First | ColumnHeuristic::RandomMrv => {}
+        }
+    }
+
+    /// Covers the columns of row y up front, as if the row were part of every solution. The
+    /// searches don't report it.
+    pub fn select_row(&mut self, y: u32) {
+        let r = self.rows[&y];
+        self.cover_row(r);
+    }
+
+    /// Seeds the random choices: RandomMrv ties and the sampling searches.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = SplitMix64::new(seed);
+    }
+
+    /// Splits the search into `count` shards and only searches shard `index`: the rows tried in
+    /// the first chosen column are dealt out to the shards in turn.
+    pub fn set_shard(&mut self, index: usize, count: usize) {
+        self.shard = Some((index, count));
+    }
+
+    // Whether the search takes the i-th row tried at the given depth
+    fn owns_branch(&self, depth: usize, i: usize) -> bool {
+        depth > 0 || self.shard.is_none_or(|(index, count)| i % count == index)
+    }
+
+    #[inline]
+    fn size(&self, col_ptr: u32) -> usize {
+        (-self.y[col_ptr as usize] - 1) as usize
+    }
+
     #[cfg(not(debug_assertions))]
     fn sanity_check(&self) {}
 
@@ -193,7 +408,7 @@ impl DLMatrix {
         }*/
     }
     /*
-        fn node_sanity_check(&self, ptr: u16) {
+        fn node_sanity_check(&self, ptr: u32) {
             if self
                 .get_neigh_ptr(self.get_neigh_ptr(ptr, Dir::Left).unwrap(), Dir::Right)
                 .unwrap()
@@ -225,7 +440,7 @@ impl DLMatrix {
             }
         }
 
-        fn column_sanity_check(&self, col_ptr: u16) {
+        fn column_sanity_check(&self, col_ptr: u32) {
             let mut j = col_ptr;
             let mut ctr = 0;
             loop {
@@ -249,25 +464,35 @@ impl DLMatrix {
     */
 
     #[inline]
-    fn unlink_left_right(&mut self, ptr: u16) {
+    fn unlink_left_right(&mut self, ptr: u32) {
         self.sanity_check();
         let left = self.get_neigh_ptr(ptr, Dir::Left);
         let right = self.get_neigh_ptr(ptr, Dir::Right);
         self.set(right, Dir::Left, left);
         self.set(left, Dir::Right, right);
+        if let Some(buckets) = &mut self.buckets {
+            if buckets.member[ptr as usize] {
+                buckets.remove(ptr, (-self.y[ptr as usize] - 1) as usize);
+            }
+        }
     }
 
     #[inline]
-    fn relink_left_right(&mut self, ptr: u16) {
+    fn relink_left_right(&mut self, ptr: u32) {
         self.sanity_check();
         let left = self.get_neigh_ptr(ptr, Dir::Left);
         let right = self.get_neigh_ptr(ptr, Dir::Right);
         self.set(right, Dir::Left, ptr);
         self.set(left, Dir::Right, ptr);
+        if let Some(buckets) = &mut self.buckets {
+            if buckets.primary[ptr as usize] {
+                buckets.insert(ptr, (-self.y[ptr as usize] - 1) as usize);
+            }
+        }
     }
 
     #[inline]
-    fn unlink_up_down(&mut self, ptr: u16) {
+    fn unlink_up_down(&mut self, ptr: u32) {
         self.sanity_check();
         let up = self.get_neigh_ptr(ptr, Dir::Up);
         let down = self.get_neigh_ptr(ptr, Dir::Down);
@@ -275,12 +500,16 @@ impl DLMatrix {
         self.set(up, Dir::Down, down);
         let col = self.get_column_ptr(ptr);
         if col != ptr {
-            self.y[ptr as usize] += 1; // Decrease size by one. Todo: separate function.
+            self.y[col as usize] += 1; // Decrease size by one. Todo: separate function.
+            if let Some(buckets) = &mut self.buckets {
+                let size = (-self.y[col as usize] - 1) as usize;
+                buckets.resize(col, size + 1, size);
+            }
         }
     }
 
     #[inline]
-    fn relink_up_down(&mut self, ptr: u16) {
+    fn relink_up_down(&mut self, ptr: u32) {
         self.sanity_check();
         let up = self.get_neigh_ptr(ptr, Dir::Up);
         let down = self.get_neigh_ptr(ptr, Dir::Down);
@@ -288,34 +517,209 @@ impl DLMatrix {
         self.set(up, Dir::Down, ptr);
         let col = self.get_column_ptr(ptr);
         if col != ptr {
-            self.y[ptr as usize] -= 1; // Increase size by one. Todo: separate function.
+            self.y[col as usize] -= 1; // Increase size by one. Todo: separate function.
+            if let Some(buckets) = &mut self.buckets {
+                let size = (-self.y[col as usize] - 1) as usize;
+                buckets.resize(col, size - 1, size);
+            }
         }
     }
 
     // Solution = set of columns' x coordinates
-    pub fn exact_cover(&mut self) -> Vec<Vec<u16>> {
-        let mut o_vals: Vec<u16> = Vec::new();
-        let mut solutions: Vec<Vec<u16>> = Vec::new();
-        self.exact_cover_rec(0, &mut o_vals, &mut solutions);
+    pub fn exact_cover(&mut self) -> Vec<Vec<u32>> {
+        let mut solutions: Vec<Vec<u32>> = Vec::new();
+        self.exact_cover_each(&mut |solution| {
+            solutions.push(solution);
+            true
+        });
         solutions
     }
 
-    fn exact_cover_rec(
+    /// Same search as `exact_cover`, handing each solution to `found` as soon as it's found
+    /// instead of collecting them.
+    pub fn exact_cover_each(&mut self, found: &mut SolutionVisitor) {
+        let mut o_vals: Vec<u32> = Vec::new();
+        self.exact_cover_rec(&mut o_vals, found);
+    }
+
+    /// Same search as `exact_cover`, skipping every node that comes before the one at `resume`
+    /// in search order; `solutions` holds the ones found in those nodes. A node is given by the
+    /// index of the row picked in each chosen column on the way to it. `on_node` sees each node's
+    /// path and the solutions found before it when the search enters it, so both together are
+    /// enough to resume from there. Panics unless the heuristic is resumable.
+    pub fn exact_cover_resumable(
+        &mut self,
+        resume: &[usize],
+        mut solutions: Vec<Vec<u32>>,
+        on_node: &mut NodeVisitor,
+    ) -> Vec<Vec<u32>> {
+        assert!(
+            self.heuristic.resumable(),
+            "can't resume a search with the {:?} heuristic",
+            self.heuristic
+        );
+        let mut o_vals: Vec<u32> = Vec::new();
+        let mut path: Vec<usize> = Vec::new();
+        self.exact_cover_resumable_rec(&mut o_vals, &mut path, resume, &mut solutions, on_node);
+        solutions
+    }
+
+    fn exact_cover_resumable_rec(
         &mut self,
-        k: u16,
-        partial_solution: &mut Vec<u16>,
-        solutions: &mut Vec<Vec<u16>>,
+        partial_solution: &mut Vec<u32>,
+        path: &mut Vec<usize>,
+        resume: &[usize],
+        solutions: &mut Vec<Vec<u32>>,
+        on_node: &mut NodeVisitor,
     ) {
-        // If the matrix A has no columns, the current partial solution is a valid solution; terminate successfully.
+        on_node(path, solutions);
         if self.get_neigh_ptr(self.root_ptr(), Dir::Right) == self.root_ptr() {
-            solutions.push(self.current_solution(partial_solution));
+            if self.owns_branch(path.len(), 0) {
+                solutions.push(self.current_solution(partial_solution));
+            }
             return;
         }
 
-        let c: u16 = self.choose_column();
+        let c: u32 = self.choose_column();
+        // Only the first row tried is still on the way to the resumed node
+        let (skip, mut rest) = match resume.split_first() {
+            Some((skip, rest)) => (*skip, rest),
+            None => (0, resume),
+        };
+        let mut r = self.get_neigh_ptr(c, Dir::Down);
+        let mut i = 0;
+        while r != c {
+            if i >= skip && self.owns_branch(path.len(), i) {
+                path.push(i);
+                partial_solution.push(r);
+                self.cover_row(r);
+                self.exact_cover_resumable_rec(partial_solution, path, rest, solutions, on_node);
+                self.uncover_row(r);
+                partial_solution.pop();
+                path.pop();
+                rest = &[];
+            }
+            r = self.get_neigh_ptr(r, Dir::Down);
+            i += 1;
+        }
+    }
+
+    /// Up to `limit` solutions, trying the rows of every chosen column in a random order.
+    /// The same seed gives the same solutions in the same order.
+    pub fn sample(&mut self, limit: usize) -> Vec<Vec<u32>> {
+        let mut o_vals: Vec<u32> = Vec::new();
+        let mut solutions: Vec<Vec<u32>> = Vec::new();
+        if limit == 0 {
+            return solutions;
+        }
+        self.shuffle_rows = true;
+        self.exact_cover_rec(&mut o_vals, &mut |solution| {
+            solutions.push(solution);
+            solutions.len() < limit
+        });
+        self.shuffle_rows = false;
+        solutions
+    }
+
+    /// Up to `limit` distinct solutions, each from a random walk down the search tree that picks
+    /// uniformly among the rows of each chosen column. A walk reaches a solution with probability
+    /// 1 / w, where w is the product of the column sizes on its path (Knuth's estimate of the tree
+    /// size), so keeping it with probability w / max_w makes every solution equally likely.
+    /// max_w is the largest w seen so far, which makes the sample only approximately uniform.
+    /// Gives up after `max_walks` walks.
+    pub fn sample_uniform(&mut self, limit: usize, max_walks: usize) -> Vec<Vec<u32>> {
+        let mut solutions: Vec<Vec<u32>> = Vec::new();
+        let mut seen = HashSet::new();
+        let mut max_weight: f64 = 0.0;
+        for _ in 0..max_walks {
+            if solutions.len() >= limit {
+                break;
+            }
+            let walk = self.random_walk();
+            let Some(solution) = walk.solution else {
+                continue;
+            };
+            let weight: f64 = walk.degrees.iter().map(|d| *d as f64).product();
+            max_weight = max_weight.max(weight);
+            if self.rng.next_f64() * max_weight < weight {
+                let mut key = solution.clone();
+                key.sort();
+                if seen.insert(key) {
+                    solutions.push(solution);
+                }
+            }
+        }
+        solutions
+    }
+
+    /// One random path from the root of the search tree, choosing uniformly among the rows of
+    /// each chosen column, as used by Knuth's estimator. The matrix is left as it was.
+    pub fn random_walk(&mut self) -> RandomWalk {
+        let mut path: Vec<u32> = Vec::new();
+        let mut degrees = Vec::new();
+        let mut found = true;
+        while self.get_neigh_ptr(self.root_ptr(), Dir::Right) != self.root_ptr() {
+            let c = self.choose_column();
+            let size = self.size(c);
+            degrees.push(size);
+            if size == 0 {
+                found = false;
+                break;
+            }
+            let mut r = self.get_neigh_ptr(c, Dir::Down);
+            for _ in 0..self.rng.below(size) {
+                r = self.get_neigh_ptr(r, Dir::Down);
+            }
+            self.cover_row(r);
+            path.push(r);
+        }
+        let solution = self.current_solution(&path);
+        for r in path.iter().rev() {
+            self.uncover_row(*r);
+        }
+        RandomWalk {
+            solution: if found { Some(solution) } else { None },
+            degrees,
+        }
+    }
+
+    // Returns false once `found` has asked to stop
+    fn exact_cover_rec(
+        &mut self,
+        partial_solution: &mut Vec<u32>,
+        found: &mut SolutionVisitor,
+    ) -> bool {
+        // If the matrix A has no columns, the current partial solution is a valid solution; terminate successfully.
+        if self.get_neigh_ptr(self.root_ptr(), Dir::Right) == self.root_ptr() {
+            if self.owns_branch(partial_solution.len(), 0) {
+                return found(self.current_solution(partial_solution));
+            }
+            return true;
+        }
+
+        let c: u32 = self.choose_column();
+
+        if self.shuffle_rows {
+            let mut rows = Vec::with_capacity(self.size(c));
+            let mut r = self.get_neigh_ptr(c, Dir::Down);
+            while r != c {
+                rows.push(r);
+                r = self.get_neigh_ptr(r, Dir::Down);
+            }
+            for i in (1..rows.len()).rev() {
+                rows.swap(i, self.rng.below(i + 1));
+            }
+            for r in rows {
+                if !self.try_row(r, partial_solution, found) {
+                    return false;
+                }
+            }
+            return true;
+        }
 
         // Try every row r that itersects the column c: (this can be parallelized if we clone the matrix)
         let mut r = c;
+        let mut i = 0;
         loop {
             r = self.get_neigh_ptr(r, Dir::Down);
 
@@ -323,39 +727,73 @@ impl DLMatrix {
                 break;
             }
 
-            // Include row r in the partial solution.
-            partial_solution.push(r);
-
-            // Every column that is handled by row r is no longer in the equation.
-            // Remove all such columns AND all rows that also intersect such columns.
-            // We say: cover all such columns.
-            let mut j = r;
-            loop {
-                self.cover(self.get_column_ptr(j));
-                j = self.get_neigh_ptr(j, Dir::Right);
-                if j == r {
-                    break;
-                }
+            if self.owns_branch(partial_solution.len(), i)
+                && !self.try_row(r, partial_solution, found)
+            {
+                return false;
             }
+            i += 1;
+        }
+        true
+    }
 
-            self.exact_cover_rec(k + 1, partial_solution, solutions);
+    fn try_row(
+        &mut self,
+        r: u32,
+        partial_solution: &mut Vec<u32>,
+        found: &mut SolutionVisitor,
+    ) -> bool {
+        // Include row r in the partial solution.
+        partial_solution.push(r);
+        self.cover_row(r);
 
-            // Undo covering the columns
-            loop {
-                j = self.get_neigh_ptr(j, Dir::Left);
-                self.uncover(self.get_column_ptr(j));
-                if j == r {
-                    break;
-                }
+        let go_on = self.exact_cover_rec(partial_solution, found);
+
+        self.uncover_row(r);
+        partial_solution.pop();
+        self.sanity_check();
+        go_on
+    }
+
+    // Every column that is handled by row r is no longer in the equation.
+    // Remove all such columns AND all rows that also intersect such columns.
+    // We say: cover all such columns.
+    fn cover_row(&mut self, r: u32) {
+        let mut j = r;
+        loop {
+            self.cover(self.get_column_ptr(j));
+            j = self.get_neigh_ptr(j, Dir::Right);
+            if j == r {
+                break;
             }
+        }
+    }
 
-            partial_solution.pop();
-            self.sanity_check();
+    // Undo covering the columns, in reverse order
+    fn uncover_row(&mut self, r: u32) {
+        let mut j = r;
+        loop {
+            j = self.get_neigh_ptr(j, Dir::Left);
+            self.uncover(self.get_column_ptr(j));
+            if j == r {
+                break;
+            }
         }
     }
 
-    fn choose_column(&self) -> u16 {
-        let mut s = i16::MAX;
+    fn choose_column(&mut self) -> u32 {
+        match self.heuristic {
+            ColumnHeuristic::Mrv => self.choose_min_size_column(),
+            ColumnHeuristic::First | ColumnHeuristic::Static => {
+                self.get_neigh_ptr(self.root_ptr(), Dir::Right)
+            }
+            ColumnHeuristic::RandomMrv => self.choose_random_min_size_column(),
+            ColumnHeuristic::Buckets => self.buckets.as_mut().unwrap().min(),
+        }
+    }
+
+    fn choose_min_size_column(&self) -> u32 {
+        let mut s = i32::MAX;
         let mut j = self.root_ptr();
         let mut c = j;
         loop {
@@ -372,8 +810,34 @@ impl DLMatrix {
         c
     }
 
+    // Uniform among the columns of minimum size (reservoir sampling over the ties)
+    fn choose_random_min_size_column(&mut self) -> u32 {
+        let mut s = usize::MAX;
+        let mut ties = 0;
+        let mut j = self.root_ptr();
+        let mut c = j;
+        loop {
+            j = self.get_neigh_ptr(j, Dir::Right);
+            if j == self.root_ptr() {
+                break;
+            }
+            let size = self.size(j);
+            if size < s {
+                s = size;
+                ties = 1;
+                c = j;
+            } else if size == s {
+                ties += 1;
+                if self.rng.below(ties) == 0 {
+                    c = j;
+                }
+            }
+        }
+        c
+    }
+
     // Cover the column: delete it and all rows that intersect it.
-    fn cover(&mut self, col_ptr: u16) {
+    fn cover(&mut self, col_ptr: u32) {
         self.unlink_left_right(col_ptr);
         let mut row_ptr = self.get_neigh_ptr(col_ptr, Dir::Down);
         while row_ptr != col_ptr {
@@ -387,7 +851,7 @@ impl DLMatrix {
     }
 
     // Uncover the column: undelete it and all rows that intersect it.
-    fn uncover(&mut self, col_ptr: u16) {
+    fn uncover(&mut self, col_ptr: u32) {
         let mut row_ptr = self.get_neigh_ptr(col_ptr, Dir::Up);
         while row_ptr != col_ptr {
             let mut j = self.get_neigh_ptr(row_ptr, Dir::Left);
@@ -401,14 +865,299 @@ impl DLMatrix {
         self.relink_left_right(col_ptr);
     }
 
-    fn current_solution(&mut self, partial_solution: &mut Vec<u16>) -> Vec<u16> {
-        let mut res: Vec<u16> = Vec::new();
+    /// Every solution of a matrix built by from_colored_rows, as lists of row y coordinates.
+    pub fn exact_cover_colored(&mut self) -> Vec<Vec<u32>> {
+        let mut o_vals: Vec<u32> = Vec::new();
+        let mut solutions: Vec<Vec<u32>> = Vec::new();
+        self.exact_cover_colored_rec(&mut o_vals, &mut solutions);
+        solutions
+    }
+
+    fn exact_cover_colored_rec(
+        &mut self,
+        partial_solution: &mut Vec<u32>,
+        solutions: &mut Vec<Vec<u32>>,
+    ) {
+        if self.get_neigh_ptr(self.root_ptr(), Dir::Right) == self.root_ptr() {
+            solutions.push(self.current_solution(partial_solution));
+            return;
+        }
+
+        let c: u32 = self.choose_column();
+        self.cover_colored(c);
+
+        let mut r = c;
+        loop {
+            r = self.get_neigh_ptr(r, Dir::Down);
+            if r == c {
+                break;
+            }
+            partial_solution.push(r);
+
+            let mut j = self.get_neigh_ptr(r, Dir::Right);
+            while j != r {
+                self.commit(j);
+                j = self.get_neigh_ptr(j, Dir::Right);
+            }
+
+            self.exact_cover_colored_rec(partial_solution, solutions);
+
+            // Undo in reverse order, so every uncommit sees the state its commit left behind
+            j = self.get_neigh_ptr(r, Dir::Left);
+            while j != r {
+                self.uncommit(j);
+                j = self.get_neigh_ptr(j, Dir::Left);
+            }
+            partial_solution.pop();
+        }
+
+        self.uncover_colored(c);
+    }
+
+    // Like cover, but cells of purified columns stay linked: their rows already agree there.
+    fn cover_colored(&mut self, col_ptr: u32) {
+        self.unlink_left_right(col_ptr);
+        let mut row_ptr = self.get_neigh_ptr(col_ptr, Dir::Down);
+        while row_ptr != col_ptr {
+            self.hide_colored(row_ptr);
+            row_ptr = self.get_neigh_ptr(row_ptr, Dir::Down);
+        }
+    }
+
+    fn uncover_colored(&mut self, col_ptr: u32) {
+        let mut row_ptr = self.get_neigh_ptr(col_ptr, Dir::Up);
+        while row_ptr != col_ptr {
+            self.unhide_colored(row_ptr);
+            row_ptr = self.get_neigh_ptr(row_ptr, Dir::Up);
+        }
+        self.relink_left_right(col_ptr);
+    }
+
+    // Remove the row of ptr (except ptr itself) from the other columns
+    fn hide_colored(&mut self, ptr: u32) {
+        let mut j = self.get_neigh_ptr(ptr, Dir::Right);
+        while j != ptr {
+            if self.color[j as usize] >= 0 {
+                self.unlink_up_down(j);
+            }
+            j = self.get_neigh_ptr(j, Dir::Right);
+        }
+    }
+
+    fn unhide_colored(&mut self, ptr: u32) {
+        let mut j = self.get_neigh_ptr(ptr, Dir::Left);
+        while j != ptr {
+            if self.color[j as usize] >= 0 {
+                self.relink_up_down(j);
+            }
+            j = self.get_neigh_ptr(j, Dir::Left);
+        }
+    }
+
+    // Take the column of ptr out of play for the chosen row: an uncolored cell covers it,
+    // a colored one purifies it. A purified cell (< 0) needs nothing, its column already agrees.
+    fn commit(&mut self, ptr: u32) {
+        match self.color[ptr as usize] {
+            0 => self.cover_colored(self.get_column_ptr(ptr)),
+            c if c > 0 => self.purify(ptr),
+            _ => {}
+        }
+    }
+
+    fn uncommit(&mut self, ptr: u32) {
+        match self.color[ptr as usize] {
+            0 => self.uncover_colored(self.get_column_ptr(ptr)),
+            c if c > 0 => self.unpurify(ptr),
+            _ => {}
+        }
+    }
+
+    // Hide the rows giving the column of ptr a different color and mark the ones giving it the
+    // same color as purified. ptr keeps its color, so uncommit knows to unpurify.
+    fn purify(&mut self, ptr: u32) {
+        let c = self.color[ptr as usize];
+        let col_ptr = self.get_column_ptr(ptr);
+        let mut j = self.get_neigh_ptr(col_ptr, Dir::Down);
+        while j != col_ptr {
+            if self.color[j as usize] != c {
+                self.hide_colored(j);
+            } else if j != ptr {
+                self.color[j as usize] = -1;
+            }
+            j = self.get_neigh_ptr(j, Dir::Down);
+        }
+    }
+
+    fn unpurify(&mut self, ptr: u32) {
+        let c = self.color[ptr as usize];
+        let col_ptr = self.get_column_ptr(ptr);
+        let mut j = self.get_neigh_ptr(col_ptr, Dir::Up);
+        while j != col_ptr {
+            if self.color[j as usize] < 0 {
+                self.color[j as usize] = c;
+            } else if j != ptr {
+                self.unhide_colored(j);
+            }
+            j = self.get_neigh_ptr(j, Dir::Up);
+        }
+    }
+
+    /// Every solution of a matrix built by from_multiplicity_rows, as lists of row y coordinates.
+    pub fn exact_cover_multiplicity(&mut self) -> Vec<Vec<u32>> {
+        let mut o_vals: Vec<u32> = Vec::new();
+        let mut solutions: Vec<Vec<u32>> = Vec::new();
+        // Columns that take no rows at all are closed up front
+        let closed: Vec<u32> = self
+            .columns
+            .values()
+            .copied()
+            .filter(|ptr| self.bounds[*ptr as usize].1 == 0)
+            .collect();
+        for col_ptr in closed.iter() {
+            self.cover(*col_ptr);
+        }
+        self.exact_cover_multiplicity_rec(&mut o_vals, &mut solutions);
+        for col_ptr in closed.iter().rev() {
+            self.uncover(*col_ptr);
+        }
+        solutions
+    }
+
+    // Branches on column c as "the next row hitting c is r", for each r in c's list, plus
+    // "no more rows hit c" once its minimum is met. Every tried r stays out of the matrix for
+    // the later branches, so each set of rows is found only once.
+    fn exact_cover_multiplicity_rec(
+        &mut self,
+        partial_solution: &mut Vec<u32>,
+        solutions: &mut Vec<Vec<u32>>,
+    ) {
+        if self.get_neigh_ptr(self.root_ptr(), Dir::Right) == self.root_ptr() {
+            solutions.push(self.current_solution(partial_solution));
+            return;
+        }
+
+        let c = match self.choose_bounded_column() {
+            Some(c) => c,
+            None => return,
+        };
+
+        let mut tried = Vec::new();
+        loop {
+            let r = self.get_neigh_ptr(c, Dir::Down);
+            if r == c {
+                break;
+            }
+            self.hide_row(r);
+            tried.push(r);
+            partial_solution.push(r);
+            self.take_row(r);
+
+            self.exact_cover_multiplicity_rec(partial_solution, solutions);
+
+            self.untake_row(r);
+            partial_solution.pop();
+        }
+
+        if self.taken[c as usize] >= self.bounds[c as usize].0 {
+            self.cover(c);
+            self.exact_cover_multiplicity_rec(partial_solution, solutions);
+            self.uncover(c);
+        }
+
+        for r in tried.into_iter().rev() {
+            self.unhide_row(r);
+        }
+    }
+
+    // The column with the fewest branches, or None if some column can no longer reach its minimum
+    fn choose_bounded_column(&self) -> Option<u32> {
+        let mut s = i32::MAX;
+        let mut j = self.root_ptr();
+        let mut c = None;
+        loop {
+            j = self.get_neigh_ptr(j, Dir::Right);
+            if j == self.root_ptr() {
+                break;
+            }
+            let size = -self.y[j as usize] - 1;
+            let needed = self.bounds[j as usize]
+                .0
+                .saturating_sub(self.taken[j as usize]) as i32;
+            if needed > size {
+                return None;
+            }
+            let branches = if needed == 0 { size + 1 } else { size };
+            if branches < s {
+                s = branches;
+                c = Some(j);
+            }
+        }
+        c
+    }
+
+    // Unlink every cell of the row of ptr, ptr included
+    fn hide_row(&mut self, ptr: u32) {
+        let mut j = ptr;
+        loop {
+            self.unlink_up_down(j);
+            j = self.get_neigh_ptr(j, Dir::Right);
+            if j == ptr {
+                break;
+            }
+        }
+    }
+
+    fn unhide_row(&mut self, ptr: u32) {
+        let mut j = ptr;
+        loop {
+            j = self.get_neigh_ptr(j, Dir::Left);
+            self.relink_up_down(j);
+            if j == ptr {
+                break;
+            }
+        }
+    }
+
+    // Count the row of ptr against its columns, covering the ones that reach their maximum
+    fn take_row(&mut self, ptr: u32) {
+        let mut j = ptr;
+        loop {
+            let col = self.get_column_ptr(j) as usize;
+            self.taken[col] += 1;
+            if self.taken[col] == self.bounds[col].1 {
+                self.cover(col as u32);
+            }
+            j = self.get_neigh_ptr(j, Dir::Right);
+            if j == ptr {
+                break;
+            }
+        }
+    }
+
+    fn untake_row(&mut self, ptr: u32) {
+        let mut j = ptr;
+        loop {
+            j = self.get_neigh_ptr(j, Dir::Left);
+            let col = self.get_column_ptr(j) as usize;
+            if self.taken[col] == self.bounds[col].1 {
+                self.uncover(col as u32);
+            }
+            self.taken[col] -= 1;
+            if j == ptr {
+                break;
+            }
+        }
+    }
+
+    fn current_solution(&mut self, partial_solution: &[u32]) -> Vec<u32> {
+        let mut res: Vec<u32> = Vec::new();
         for &ptr in partial_solution.iter() {
-            res.push(self.y[ptr as usize] as u16);
+            res.push(self.y[ptr as usize] as u32);
         }
         res
     }
 
+    #[cfg(test)]
     fn print(&self) {
         let root_ptr = self.root_ptr();
         let mut columns = HashMap::new();
@@ -483,199 +1232,121 @@ impl DLMatrix {
     }
 }
 
-#[test]
-fn test_create_dlmatrix() {
-    let columns = vec![
-        vec![true, false, true],
-        vec![false, true, false],
-        vec![true, true, true],
-    ];
-    let m = DLMatrix::from_bool_columns(&columns);
-    dbg!(m);
-    // TODO: add a way to traverse the matrix
-}
+impl ExactCover for DLMatrix {
+    fn from_bool_rows(rows: &[Vec<bool>]) -> Self {
+        DLMatrix::from_bool_rows(rows)
+    }
 
-#[test]
-fn test_simple_exact_cover() {
-    let columns = vec![
-        vec![true, false, true],
-        vec![true, true, false],
-        vec![false, true, false],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 1);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        dbg!(&solution);
-        println!("");
+    #[cfg(test)]
+    fn from_bool_columns(columns: &[Vec<bool>]) -> Self {
+        DLMatrix::from_bool_columns(columns)
     }
-}
 
-#[test]
-fn test_simple_exact_cover_3el_col() {
-    let columns = vec![
-        vec![true, true, true],
-        vec![false, true, false],
-        vec![false, true, true],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 1);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        dbg!(&solution);
-        println!("");
+    fn solutions(&mut self) -> Vec<Vec<usize>> {
+        self.exact_cover()
+            .into_iter()
+            .map(|solution| solution.into_iter().map(|y| y as usize).collect())
+            .collect()
     }
-}
 
-#[test]
-fn test_simple_exact_cover_multi_solution() {
-    let columns = vec![
-        vec![true, false, true],
-        vec![true, false, true],
-        vec![false, true, true],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 2);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        dbg!(&solution);
-        println!("");
+    #[cfg(test)]
+    fn print(&self) {
+        DLMatrix::print(self)
     }
 }
 
 #[test]
-fn test_medium_exact_cover() {
-    let columns = vec![
-        vec![false, true, false, true, false, false],
-        vec![false, false, true, false, true, false],
-        vec![true, false, true, false, false, false],
-        vec![false, true, false, true, false, true],
-        vec![true, false, false, false, false, true],
-        vec![true, false, true, false, false, false],
-        vec![false, true, false, false, false, true],
+fn test_colored_exact_cover() {
+    // Knuth's example for Algorithm C: primary items p q r, secondary items x y; colors A=1, B=2
+    let (p, q, r, x, y) = (0, 1, 2, 3, 4);
+    let rows = vec![
+        vec![(p, 0), (q, 0), (x, 0), (y, 1)],
+        vec![(p, 0), (r, 0), (x, 1), (y, 0)],
+        vec![(p, 0), (x, 2)],
+        vec![(q, 0), (x, 1)],
+        vec![(r, 0), (y, 2)],
     ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-
-    dbg!(&m);
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
-    assert_eq!(solutions.len(), 1);
-    for (i, solution) in solutions.iter().enumerate() {
-        println!("Solution {}", i);
-        for row in solution.iter() {
-            print!("{}", *row);
-        }
-        println!("");
+    let mut m = DLMatrix::from_colored_rows(3, &rows);
+    let mut solutions = m.exact_cover_colored();
+    for solution in solutions.iter_mut() {
+        solution.sort();
     }
+    assert_eq!(solutions, vec![vec![1, 3]]);
+    // The search leaves the colors as it found them
+    assert_eq!(m.exact_cover_colored().len(), 1);
 }
 
 #[test]
-fn test_weird_exact_cover() {
-    let columns = vec![
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![true],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![false],
-        vec![true],
-    ];
-    let mut m = DLMatrix::from_bool_columns(&columns);
-    dbg!(&m);
-    m.print();
-    let solutions = m.exact_cover();
-    println!("Solutions size: {}", solutions.len());
+fn test_colored_exact_cover_many_rows() {
+    // Past the 32767 rows and 65535 nodes of 16-bit indices. Column 1 has a single row, which
+    // gives the secondary column 2 color 1, and leaves the rows of column 0 that agree on it.
+    let mut rows: Vec<Vec<(u32, i32)>> =
+        (0..40_000).map(|y| vec![(0, 0), (2, y % 3 + 1)]).collect();
+    rows.push(vec![(1, 0), (2, 1)]);
+    let mut m = DLMatrix::from_colored_rows(2, &rows);
+    let solutions = m.exact_cover_colored();
+    assert_eq!(solutions.len(), 13_334);
+    assert!(solutions.iter().all(|solution| solution.contains(&40_000)));
+    assert_eq!(
+        solutions.iter().map(|solution| solution[1]).max(),
+        Some(39_999)
+    );
 }
 
-fn transpose<T>(v: Vec<Vec<T>>) -> Vec<Vec<T>> {
-    assert!(!v.is_empty());
-    let len = v[0].len();
-    let mut iters: Vec<_> = v.into_iter().map(|n| n.into_iter()).collect();
-    (0..len)
-        .map(|_| {
-            iters
-                .iter_mut()
-                .map(|n| n.next().unwrap())
-                .collect::<Vec<T>>()
-        })
-        .collect()
-}
-
-pub fn dlx_words(words: Vec<String>) {
-    let (repr_map, reprs) = build_word_representations(&words);
-
-    let mut rows: Vec<Vec<bool>> = reprs
-        .iter()
-        .map(|w| (0..26).map(|b| (w & (1 << b)) != 0).collect())
+#[test]
+fn test_exact_cover_resumable() {
+    let rows: Vec<Vec<bool>> = (0..64u32)
+        .map(|w| (0..6).map(|b| w & (1 << b) != 0).collect())
         .collect();
+    for heuristic in ColumnHeuristic::ALL {
+        let matrix = || {
+            let mut m = DLMatrix::from_bool_rows(&rows);
+            m.set_heuristic(heuristic);
+            m.set_seed(7);
+            m
+        };
+        if !heuristic.resumable() {
+            let resumed = std::panic::catch_unwind(|| {
+                matrix().exact_cover_resumable(&[], Vec::new(), &mut |_, _| ())
+            });
+            assert!(resumed.is_err(), "{:?}", heuristic);
+            continue;
+        }
+        let expected = matrix().exact_cover();
+        assert_eq!(expected.len(), 203); // Bell number B6
 
-    for row in rows.iter_mut() {
-        row.push(false); // Column for 1-letter long dummy
-                         // We need a dummy because we need to cover all 26 letters
-                         // The last column of the matrix will represent "is this row a dummy". We need exactly one dummy row in each solution.
-    }
-    for dummy_letter in b'a'..=b'z' {
-        let mut dummy_row: Vec<bool> = (b'a'..=b'z').map(|b| b == dummy_letter).collect();
-        dummy_row.push(true);
-        rows.push(dummy_row);
-    }
-
-    // Construct the matrix and run exact cover
-    let mut dlm = DLMatrix::from_bool_rows(&rows);
-    let solutions = dlm.exact_cover();
-    let mut ctr = 0;
-    for solution in solutions.iter() {
-        let repr_combo: Vec<&u32> = solution
-            .iter()
-            .filter_map(|index| reprs.get(*index as usize))
-            .collect();
-        ctr += combo_pretty_print(repr_combo, &repr_map);
+        // Note where the search stands at every node, then resume from each of them
+        let mut stops: Vec<(Vec<usize>, Vec<Vec<u32>>)> = Vec::new();
+        let all = matrix().exact_cover_resumable(&[], Vec::new(), &mut |path, solutions| {
+            stops.push((path.to_vec(), solutions.to_vec()))
+        });
+        assert_eq!(all, expected, "{:?}", heuristic);
+        for (path, solutions) in stops.into_iter().step_by(37) {
+            let resumed = matrix().exact_cover_resumable(&path, solutions, &mut |_, _| ());
+            assert_eq!(resumed, expected, "{:?} from {:?}", heuristic, path);
+        }
     }
-    println!("Solutions count: {}", ctr);
 }
 
-/// Returns number of combos printed
-fn combo_pretty_print(combo: Vec<&u32>, reprs: &HashMap<u32, Vec<&str>>) -> usize {
-    let mut ctr = 0;
-    for word_combo in combo
-        .iter()
-        .map(|key| reprs.get(key).unwrap())
-        .multi_cartesian_product()
-    {
-        let word_combo = word_combo.into_iter().fold(String::new(), |mut acc, word| {
-            acc.push_str(word);
-            acc.push_str(" ");
-            acc
-        });
-        let _ = writeln!(std::io::stdout(), "{}", word_combo);
-        ctr += 1;
+#[test]
+fn test_multiplicity_exact_cover() {
+    // Column 0 takes one or two rows, column 1 exactly one
+    let rows = vec![vec![0], vec![0], vec![0, 1], vec![1]];
+    let mut m = DLMatrix::from_multiplicity_rows(&[(1, 2), (1, 1)], &rows);
+    let mut solutions = m.exact_cover_multiplicity();
+    for solution in solutions.iter_mut() {
+        solution.sort();
     }
-    ctr
+    solutions.sort();
+    assert_eq!(
+        solutions,
+        vec![
+            vec![0, 1, 3],
+            vec![0, 2],
+            vec![0, 3],
+            vec![1, 2],
+            vec![1, 3],
+            vec![2]
+        ]
+    );
 }
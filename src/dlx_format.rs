@@ -0,0 +1,302 @@
+// Exact cover problems in the text format of Knuth's DLX programs.
+//
+// The first line names the items, the primary ones first, then `|` and the secondary ones.
+// Each further line is an option listing its items; a secondary item may be given a color as
+// `item:color`, and options that agree on an item's color may share it. Lines starting with `|`
+// are comments.
+//
+// export-dlx writes the letter matrix dlx_words solves (items a to z and `dummy`), with a comment
+// naming each option's words; a word file ending in .dlx is read as a problem instead and its
+// solutions are printed, one option per line with a blank line after each solution.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::constraints::Constraints;
+use crate::dancing_links_soa::DLMatrix;
+use crate::exact_cover::letter_rows;
+use crate::options::Options;
+use crate::word_reprs::build_word_representations;
+
+// DLMatrix indexes its nodes, rows and columns with u32s and its colors with i32s. Options,
+// items and colors each take at least one node, so bounding the nodes bounds them all.
+const MAX_NODES: usize = i32::MAX as usize;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct DlxProblem {
+    pub primary: Vec<String>,
+    pub secondary: Vec<String>,
+    /// Items of each option, as (index into primary then secondary, color); color 0 is none
+    pub options: Vec<Vec<(u32, i32)>>,
+    /// Name of color i + 1
+    pub colors: Vec<String>,
+}
+
+impl DlxProblem {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('|'));
+        let Some((_, items)) = lines.next() else {
+            return Err("no items line".to_string());
+        };
+        let mut problem = DlxProblem::default();
+        let mut secondary = false;
+        for name in items.split_whitespace() {
+            if name == "|" {
+                if secondary {
+                    return Err("more than one | in the items line".to_string());
+                }
+                secondary = true;
+            } else if name.contains(':') || name.contains('|') {
+                return Err(format!("invalid item name {}", name));
+            } else if secondary {
+                problem.secondary.push(name.to_string());
+            } else {
+                problem.primary.push(name.to_string());
+            }
+        }
+        let items: HashMap<&str, u32> = problem
+            .primary
+            .iter()
+            .chain(problem.secondary.iter())
+            .enumerate()
+            .map(|(i, name)| (name.as_str(), i as u32))
+            .collect();
+        if items.len() < problem.primary.len() + problem.secondary.len() {
+            return Err("an item is named twice".to_string());
+        }
+        if problem.primary.is_empty() {
+            return Err("no primary items".to_string());
+        }
+        if items.len() > MAX_NODES {
+            return Err(format!("more than {} items", MAX_NODES));
+        }
+
+        let mut colors: HashMap<String, i32> = HashMap::new();
+        // The matrix has a node per item, one per item of each option and a root
+        let mut nodes = items.len() + 1;
+        for (line_no, line) in lines {
+            let mut option: Vec<(u32, i32)> = Vec::new();
+            for field in line.split_whitespace() {
+                let (name, color) = match field.split_once(':') {
+                    Some((name, color)) => (name, Some(color)),
+                    None => (field, None),
+                };
+                let Some(item) = items.get(name) else {
+                    return Err(format!("line {}: unknown item {}", line_no, name));
+                };
+                if option.iter().any(|(x, _)| x == item) {
+                    return Err(format!("line {}: item {} twice", line_no, name));
+                }
+                let color = match color {
+                    None => 0,
+                    Some(_) if (*item as usize) < problem.primary.len() => {
+                        return Err(format!(
+                            "line {}: primary item {} has a color",
+                            line_no, name
+                        ))
+                    }
+                    Some(color) => {
+                        let next = colors.len() as i32 + 1;
+                        *colors.entry(color.to_string()).or_insert(next)
+                    }
+                };
+                option.push((*item, color));
+            }
+            nodes += option.len();
+            if nodes > MAX_NODES {
+                return Err(format!(
+                    "line {}: the options need more than {} matrix nodes",
+                    line_no, MAX_NODES
+                ));
+            }
+            problem.options.push(option);
+        }
+        problem.colors = vec![String::new(); colors.len()];
+        for (name, color) in colors {
+            problem.colors[color as usize - 1] = name;
+        }
+        Ok(problem)
+    }
+
+    fn item_name(&self, item: u32) -> &str {
+        let item = item as usize;
+        match item.checked_sub(self.primary.len()) {
+            None => &self.primary[item],
+            Some(i) => &self.secondary[i],
+        }
+    }
+
+    /// Option `y` as a line of the format.
+    pub fn option_line(&self, y: usize) -> String {
+        let fields: Vec<String> = self.options[y]
+            .iter()
+            .map(|(item, color)| match color {
+                0 => self.item_name(*item).to_string(),
+                color => format!(
+                    "{}:{}",
+                    self.item_name(*item),
+                    self.colors[*color as usize - 1]
+                ),
+            })
+            .collect();
+        fields.join(" ")
+    }
+
+    /// The problem in the text format, with `labels` (one per option, if given) as comments.
+    pub fn to_text(&self, labels: Option<&[String]>) -> String {
+        let mut text = self.primary.join(" ");
+        if !self.secondary.is_empty() {
+            text.push_str(" | ");
+            text.push_str(&self.secondary.join(" "));
+        }
+        text.push('\n');
+        for y in 0..self.options.len() {
+            if let Some(labels) = labels {
+                text.push_str(&format!("| {}\n", labels[y]));
+            }
+            text.push_str(&self.option_line(y));
+            text.push('\n');
+        }
+        text
+    }
+
+    pub fn matrix(&self) -> DLMatrix {
+        DLMatrix::from_colored_rows(self.primary.len() as u32, &self.options)
+    }
+}
+
+/// The letter matrix of dlx_words: one option per key and one per dummy letter.
+pub fn letter_problem(keys: &[u32], skippable: u32) -> DlxProblem {
+    let mut primary: Vec<String> = (b'a'..=b'z').map(|l| (l as char).to_string()).collect();
+    primary.push("dummy".to_string());
+    let options = letter_rows(keys, skippable)
+        .iter()
+        .map(|row| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, cell)| **cell)
+                .map(|(x, _)| (x as u32, 0))
+                .collect()
+        })
+        .collect();
+    DlxProblem {
+        primary,
+        options,
+        ..DlxProblem::default()
+    }
+}
+
+/// `export-dlx <out>`: writes the exact cover problem dlx_words would solve for the word list.
+pub fn export_dlx(words: &[String], args: &[String], options: &Options) {
+    let out = match args.first() {
+        Some(out) => Path::new(out),
+        None => panic!("usage: <wordfile> export-dlx <out>"),
+    };
+    let constraints = Constraints::new(options);
+    let (reprs, keys) = build_word_representations(words);
+    let keys = constraints.candidate_keys(&keys);
+    let problem = letter_problem(&keys, constraints.skippable());
+    let mut labels: Vec<String> = keys.iter().map(|key| reprs[key].join(" ")).collect();
+    labels.extend(
+        problem.options[keys.len()..]
+            .iter()
+            .map(|option| format!("dummy {}", problem.item_name(option[0].0))),
+    );
+    if let Err(why) = fs::write(out, problem.to_text(Some(&labels))) {
+        panic!("couldn't write {}: {}", out.display(), why);
+    }
+    eprintln!(
+        "dlx: {} items, {} options",
+        problem.primary.len(),
+        problem.options.len()
+    );
+}
+
+/// Solves the problem in a .dlx file and prints its solutions.
+pub fn solve_dlx_file(path: &Path, bytes: &[u8]) {
+    let problem = match DlxProblem::parse(&String::from_utf8_lossy(bytes)) {
+        Err(why) => panic!("couldn't parse {}: {}", path.display(), why),
+        Ok(problem) => problem,
+    };
+    let solutions = problem.matrix().exact_cover_colored();
+    for solution in solutions.iter() {
+        for y in solution {
+            println!("{}", problem.option_line(*y as usize));
+        }
+        println!();
+    }
+    println!("Solutions count: {}", solutions.len());
+}
+
+#[test]
+fn test_parse_dlx() {
+    // Knuth's example for colors, as in test_colored_exact_cover
+    let text = "| a comment\np q r | x y\np q x y:A\np r x:A y\np x:B\n\nq x:A\nr y:B\n";
+    let problem = DlxProblem::parse(text).unwrap();
+    assert_eq!(problem.primary, vec!["p", "q", "r"]);
+    assert_eq!(problem.secondary, vec!["x", "y"]);
+    assert_eq!(problem.options[0], vec![(0, 0), (1, 0), (3, 0), (4, 1)]);
+    assert_eq!(problem.option_line(4), "r y:B");
+    let mut solutions = problem.matrix().exact_cover_colored();
+    solutions[0].sort();
+    assert_eq!(solutions, vec![vec![1, 3]]);
+    assert_eq!(DlxProblem::parse(&problem.to_text(None)).unwrap(), problem);
+
+    assert!(DlxProblem::parse("p q\np z\n").is_err());
+    assert!(DlxProblem::parse("p q | x\np:A\n").is_err());
+    assert!(DlxProblem::parse("p p\n").is_err());
+}
+
+#[test]
+fn test_parse_large_dlx() {
+    // More options and colors than 16-bit indices can hold
+    let count = 70_000;
+    let mut text = String::from("p | x\n");
+    for i in 0..count {
+        text.push_str(&format!("p x:c{}\n", i));
+    }
+    let problem = DlxProblem::parse(&text).unwrap();
+    assert_eq!(
+        (problem.options.len(), problem.colors.len()),
+        (count, count)
+    );
+    assert_eq!(
+        problem.option_line(count - 1),
+        format!("p x:c{}", count - 1)
+    );
+    let solutions = problem.matrix().exact_cover_colored();
+    assert_eq!(solutions.len(), count);
+    assert_eq!(solutions.iter().map(|s| s[0]).max(), Some(count as u32 - 1));
+}
+
+#[test]
+fn test_letter_problem_round_trip() {
+    use crate::word_reprs::*;
+
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let expected = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    let problem = letter_problem(&keys, (1 << 26) - 1);
+    let problem = DlxProblem::parse(&problem.to_text(None)).unwrap();
+    let mut found: Vec<[u32; 5]> = problem
+        .matrix()
+        .exact_cover_colored()
+        .iter()
+        .map(|solution| {
+            let mut combo = [0; 5];
+            let rows = solution.iter().filter_map(|y| keys.get(*y as usize));
+            for (slot, key) in combo.iter_mut().zip(rows) {
+                *slot = *key;
+            }
+            combo.sort();
+            combo
+        })
+        .collect();
+    found.sort();
+    assert_eq!(found, expected);
+}
@@ -0,0 +1,146 @@
+// Estimates the size of the dlx search tree with Knuth's random probes, without running it.
+//
+// A probe walks from the root to a leaf, choosing uniformly among the children of each node. With
+// d1, d2, ..., dk the numbers of children along its path, 1 + d1 + d1*d2 + ... + d1*...*dk is an
+// unbiased estimate of the number of nodes in the tree, and d1*...*dk one of the number of
+// solutions if the walk ends in a solution (0 if it ends in a dead end).
+
+use prettytable::{Cell, Row, Table};
+use std::time::Instant;
+
+use crate::constraints::Constraints;
+use crate::dancing_links_soa::DLMatrix;
+use crate::exact_cover::letter_matrix;
+use crate::options::Options;
+use crate::word_reprs::build_word_representations;
+
+const DEFAULT_PROBES: usize = 10_000;
+
+/// Mean of the probes with the half-width of its 95% confidence interval
+#[derive(Debug, Clone, Copy)]
+pub struct Estimate {
+    pub mean: f64,
+    pub half_width: f64,
+}
+
+impl Estimate {
+    fn from_samples(samples: &[f64]) -> Self {
+        debug_assert!(!samples.is_empty(), "an estimate needs at least one probe");
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = if samples.len() > 1 {
+            samples.iter().map(|x| (x - mean) * (x - mean)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Estimate {
+            mean,
+            half_width: 1.96 * (variance / n).sqrt(),
+        }
+    }
+
+    fn scaled(self, factor: f64) -> Self {
+        Estimate {
+            mean: self.mean * factor,
+            half_width: self.half_width * factor,
+        }
+    }
+}
+
+pub struct TreeEstimate {
+    pub nodes: Estimate,
+    pub solutions: Estimate,
+    /// Time the probes took per node they visited, a stand-in for the cost of a search node
+    pub seconds_per_node: f64,
+}
+
+pub fn estimate_tree(matrix: &mut DLMatrix, probes: usize) -> TreeEstimate {
+    let mut nodes = Vec::with_capacity(probes);
+    let mut solutions = Vec::with_capacity(probes);
+    let mut visited = 0;
+    let start = Instant::now();
+    for _ in 0..probes {
+        let walk = matrix.random_walk();
+        let mut level_width = 1.0;
+        let mut tree_nodes = 1.0;
+        for degree in walk.degrees.iter() {
+            level_width *= *degree as f64;
+            tree_nodes += level_width;
+        }
+        nodes.push(tree_nodes);
+        solutions.push(if walk.solution.is_some() {
+            level_width
+        } else {
+            0.0
+        });
+        visited += walk.degrees.len() + 1;
+    }
+    TreeEstimate {
+        nodes: Estimate::from_samples(&nodes),
+        solutions: Estimate::from_samples(&solutions),
+        seconds_per_node: start.elapsed().as_secs_f64() / visited as f64,
+    }
+}
+
+/// Prints the estimates for the whole search; the probes walk the full tree, so --shard can't
+/// narrow them down to one part of it.
+pub fn estimate(words: Vec<String>, options: &Options) {
+    assert!(
+        options.shard.is_none(),
+        "estimate covers the whole search tree, so it doesn't take --shard"
+    );
+    let (_reprs, keys) = build_word_representations(&words);
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        println!("No solutions: the constraints contradict each other");
+        return;
+    }
+    let mut matrix = letter_matrix(&constraints.candidate_keys(&keys), &constraints, options);
+    let probes = options.probes.unwrap_or(DEFAULT_PROBES);
+    let tree = estimate_tree(&mut matrix, probes);
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("quantity"),
+        Cell::new("estimate"),
+        Cell::new("95% interval"),
+    ]));
+    for (name, estimate) in [
+        ("nodes", tree.nodes),
+        ("solutions", tree.solutions),
+        (
+            "time [ms]",
+            tree.nodes.scaled(tree.seconds_per_node * 1000.0),
+        ),
+    ] {
+        table.add_row(Row::new(vec![
+            Cell::new(name),
+            Cell::new(&format!("{:.1}", estimate.mean)),
+            Cell::new(&format!(
+                "{:.1} .. {:.1}",
+                (estimate.mean - estimate.half_width).max(0.0),
+                estimate.mean + estimate.half_width
+            )),
+        ]));
+    }
+    println!(
+        "{} probes of the dlx tree (solutions are key combos)",
+        probes
+    );
+    table.printstd();
+}
+
+#[test]
+fn test_estimate_tree() {
+    use crate::word_reprs::*;
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let mut matrix = letter_matrix(&keys, &Constraints::default(), &Options::default());
+    let solutions = matrix.exact_cover().len() as f64;
+
+    let tree = estimate_tree(&mut matrix, 20_000);
+    assert!((tree.solutions.mean - solutions).abs() <= 2.0 * tree.solutions.half_width);
+    assert!(tree.nodes.mean > solutions);
+    // The probes leave the matrix as they found it
+    assert_eq!(matrix.exact_cover().len() as f64, solutions);
+}
@@ -0,0 +1,829 @@
+// Exact cover interface shared by the dancing links implementations, and the word-level
+// front end that builds the 26-letter matrix for them.
+//
+// dancing_links keeps every node as an enum in one arena (Array-of-Structs), dancing_links_soa
+// keeps one array per link direction (Struct-of-Arrays); --dlx-backend picks between them.
+
+use std::collections::HashMap;
+
+use crate::checkpoint::Checkpoint;
+use crate::constraints::Constraints;
+use crate::options::Options;
+use crate::word_reprs::{
+    build_word_representations, combo_word_count, get_repr, print_combo, ComboSink,
+};
+use crate::{dancing_links, dancing_links_soa};
+
+pub trait ExactCover: std::fmt::Debug {
+    fn from_bool_rows(rows: &[Vec<bool>]) -> Self
+    where
+        Self: Sized;
+
+    /// Prefer from_bool_rows as more performant
+    #[cfg(test)]
+    fn from_bool_columns(columns: &[Vec<bool>]) -> Self
+    where
+        Self: Sized;
+
+    /// Every exact cover of the matrix, each given as the y coordinates of its rows
+    fn solutions(&mut self) -> Vec<Vec<usize>>;
+
+    /// Prints the matrix as a table of node pointers
+    #[cfg(test)]
+    fn print(&self);
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DlxBackend {
+    Aos,
+    #[default]
+    Soa,
+}
+
+impl DlxBackend {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "aos" => Some(DlxBackend::Aos),
+            "soa" => Some(DlxBackend::Soa),
+            _ => None,
+        }
+    }
+}
+
+/// [min, max] number of times each letter a..z may be used by a combo
+pub type LetterBounds = [(u16, u16); 26];
+
+/// Parses comma-separated `letters=min..max` entries, e.g. `aeiou=0..2,q=0..0`. Letters not
+/// mentioned keep the default of at most once; a later entry overrides an earlier one.
+pub fn parse_letter_bounds(spec: &str) -> Option<LetterBounds> {
+    let mut bounds = [(0, 1); 26];
+    for entry in spec.split(',') {
+        let (letters, range) = entry.split_once('=')?;
+        let (min, max) = range.split_once("..")?;
+        let (min, max): (u16, u16) = (min.parse().ok()?, max.parse().ok()?);
+        if min > max || letters.is_empty() {
+            return None;
+        }
+        for letter in letters.bytes() {
+            if !letter.is_ascii_lowercase() {
+                return None;
+            }
+            bounds[(letter - b'a') as usize] = (min, max);
+        }
+    }
+    Some(bounds)
+}
+
+/// How dancing_links_soa picks the column to branch on
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColumnHeuristic {
+    /// Minimum remaining values: the smallest column, found by a scan over the header list
+    #[default]
+    Mrv,
+    /// The first column of the header list
+    First,
+    /// A fixed order, by column size before the search (letter frequency for the word matrix)
+    Static,
+    /// The smallest column, ties broken at random from --seed
+    RandomMrv,
+    /// The smallest column, kept in buckets by size instead of scanning
+    Buckets,
+}
+
+impl ColumnHeuristic {
+    pub const ALL: [ColumnHeuristic; 5] = [
+        ColumnHeuristic::Mrv,
+        ColumnHeuristic::First,
+        ColumnHeuristic::Static,
+        ColumnHeuristic::RandomMrv,
+        ColumnHeuristic::Buckets,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "mrv" => Some(ColumnHeuristic::Mrv),
+            "first" => Some(ColumnHeuristic::First),
+            "static" => Some(ColumnHeuristic::Static),
+            "random" => Some(ColumnHeuristic::RandomMrv),
+            "buckets" => Some(ColumnHeuristic::Buckets),
+            _ => None,
+        }
+    }
+
+    /// Whether the column chosen at a node only depends on the rows covered on the way to it,
+    /// which a resumed search needs to find the saved node again. RandomMrv draws from an RNG
+    /// the skipped nodes would have advanced, and the bucket order follows the cover history.
+    pub fn resumable(self) -> bool {
+        matches!(
+            self,
+            ColumnHeuristic::Mrv | ColumnHeuristic::First | ColumnHeuristic::Static
+        )
+    }
+}
+
+/// How --sample picks its solutions. Both draw anagram-class combos, not word-level solutions,
+/// and each class is printed as one of its words.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Sampler {
+    /// The first solutions of a search with shuffled branch order
+    #[default]
+    Shuffle,
+    /// Random walks with rejection, approximately uniform over all class combos
+    Uniform,
+}
+
+impl Sampler {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "shuffle" => Some(Sampler::Shuffle),
+            "uniform" => Some(Sampler::Uniform),
+            _ => None,
+        }
+    }
+}
+
+// Random walks allowed per requested sample before sample_uniform gives up
+const UNIFORM_WALKS_PER_SAMPLE: usize = 100_000;
+
+pub fn dlx_words(words: Vec<String>, options: &Options) {
+    if options.letter_bounds.is_some() {
+        let solutions = dlx_bounded_words(&words, options);
+        for solution in solutions.iter() {
+            println!("{} ", solution.join(" "));
+        }
+        println!("Solutions count: {}", solutions.len());
+        return;
+    }
+    let (mut repr_map, reprs) = build_word_representations(&words);
+    if options.sample.is_some() {
+        sampled_classes(&mut repr_map);
+    }
+
+    let solutions = dlx_solutions(&reprs, options);
+    let mut ctr = 0;
+    for solution in solutions.iter() {
+        let mut combo = [0u32; 5];
+        for (slot, key) in combo.iter_mut().zip(solution.iter()) {
+            *slot = *key;
+        }
+        print_combo(combo, &repr_map);
+        ctr += combo_word_count(&combo, &repr_map);
+    }
+    println!("Solutions count: {}", ctr);
+}
+
+/// Keeps one word of each class, so that a combo sampled by --sample prints as one solution.
+pub fn sampled_classes(reprs: &mut HashMap<u32, Vec<&str>>) {
+    reprs.values_mut().for_each(|words| words.truncate(1));
+}
+
+/// Streams the plain SoA search's solutions to `emit` as they're found, so that a sink can stop
+/// it early (serve does). The AoS backend, --resume and --sample collect every solution first.
+/// --letter-bounds finds words rather than anagram classes, see `dlx_bounded_words`.
+pub fn dlx_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    if options.dlx_backend == DlxBackend::Soa
+        && options.resume.is_none()
+        && options.sample.is_none()
+        && options.letter_bounds.is_none()
+    {
+        let constraints = Constraints::new(options);
+        if !constraints.feasible() {
+            return;
+        }
+        let keys = constraints.candidate_keys(keys);
+        let mut matrix = letter_matrix(&keys, &constraints, options);
+        matrix.exact_cover_each(&mut |solution| {
+            let mut combo = [0u32; 5];
+            let solution_keys = solution
+                .iter()
+                .filter_map(|y| keys.get(*y as usize))
+                .chain(constraints.required.iter());
+            for (slot, key) in combo.iter_mut().zip(solution_keys) {
+                *slot = *key;
+            }
+            emit(combo);
+            true
+        });
+        return;
+    }
+    for solution in dlx_solutions(keys, options).iter() {
+        let mut combo = [0u32; 5];
+        for (slot, key) in combo.iter_mut().zip(solution.iter()) {
+            *slot = *key;
+        }
+        emit(combo);
+    }
+}
+
+/// Runs exact cover over the 26 letters and returns the keys of each solution.
+/// Constraints shape the matrix: only the keys that fit with the included words get rows, only
+/// the letters a solution may leave out get dummy rows, and the SoA matrix starts with the
+/// included words' rows selected.
+fn dlx_solutions(reprs: &[u32], options: &Options) -> Vec<Vec<u32>> {
+    assert!(
+        options.letter_bounds.is_none(),
+        "--letter-bounds can pick two anagrams together, so it only works with the dlx mode"
+    );
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return Vec::new();
+    }
+    let keys = constraints.candidate_keys(reprs);
+    if options.dlx_backend == DlxBackend::Aos {
+        // The AoS matrix only has the plain MRV search
+        assert!(
+            options.resume.is_none() && options.shard.is_none() && options.sample.is_none(),
+            "--dlx-backend aos can't be combined with --resume, --shard or --sample"
+        );
+        assert!(
+            options.dlx_heuristic == ColumnHeuristic::Mrv,
+            "--dlx-backend aos only supports --dlx-heuristic mrv"
+        );
+    }
+    if options.resume.is_some() {
+        assert!(
+            options.dlx_heuristic.resumable(),
+            "--resume only works with --dlx-heuristic mrv, first or static"
+        );
+    }
+    if options.resume.is_some() || options.shard.is_some() {
+        assert!(
+            options.sample.is_none(),
+            "--resume and --shard can't be combined with --sample"
+        );
+    }
+
+    // Construct the matrix and run exact cover
+    let (solutions, selected) = match options.dlx_backend {
+        DlxBackend::Aos => {
+            let rows = letter_rows(&keys, constraints.skippable());
+            (
+                dancing_links::DLMatrix::from_bool_rows(&rows).solutions(),
+                &[][..],
+            )
+        }
+        DlxBackend::Soa => {
+            let mut matrix = letter_matrix(&keys, &constraints, options);
+            // The saved path depends on the column choices, and so on the heuristic and seed
+            let solver = format!(
+                "dlx-{:?}-{}",
+                options.dlx_heuristic,
+                options.seed.unwrap_or(0)
+            );
+            let solutions = match (options.sample, options.sampler) {
+                (None, _) => match Checkpoint::open(options, &solver, &keys) {
+                    Some(checkpoint) => checkpoint.exact_cover(&mut matrix),
+                    None => matrix.exact_cover(),
+                },
+                (Some(n), Sampler::Shuffle) => matrix.sample(n),
+                (Some(n), Sampler::Uniform) => {
+                    matrix.sample_uniform(n, n.saturating_mul(UNIFORM_WALKS_PER_SAMPLE))
+                }
+            };
+            let solutions = solutions
+                .into_iter()
+                .map(|solution| solution.into_iter().map(|y| y as usize).collect())
+                .collect();
+            (solutions, &constraints.required[..])
+        }
+    };
+    solutions
+        .into_iter()
+        .map(|solution: Vec<usize>| {
+            solution
+                .iter()
+                .filter_map(|y| keys.get(*y))
+                .chain(selected.iter())
+                .copied()
+                .collect()
+        })
+        .collect()
+}
+
+/// The SoA matrix for `keys` (from `Constraints::candidate_keys`), with --dlx-heuristic and
+/// --seed applied and the rows of the included keys selected.
+pub fn letter_matrix(
+    keys: &[u32],
+    constraints: &Constraints,
+    options: &Options,
+) -> dancing_links_soa::DLMatrix {
+    let mut matrix =
+        dancing_links_soa::DLMatrix::from_bool_rows(&letter_rows(keys, constraints.skippable()));
+    for key in constraints.required.iter() {
+        let y = keys.binary_search(key).unwrap();
+        matrix.select_row(y as u32);
+    }
+    matrix.set_heuristic(options.dlx_heuristic);
+    matrix.set_seed(options.seed.unwrap_or(0));
+    if let Some(shard) = options.shard {
+        matrix.set_shard(shard.index, shard.count);
+    }
+    matrix
+}
+
+/// Rows of the 26-letter matrix: one per key, then a dummy row for each letter in `skippable`.
+pub fn letter_rows(reprs: &[u32], skippable: u32) -> Vec<Vec<bool>> {
+    let mut rows: Vec<Vec<bool>> = reprs
+        .iter()
+        .map(|w| (0..26).map(|b| (w & (1 << b)) != 0).collect())
+        .collect();
+
+    for row in rows.iter_mut() {
+        row.push(false); // Column for 1-letter long dummy
+                         // We need a dummy because we need to cover all 26 letters
+                         // The last column of the matrix will represent "is this row a dummy". We need exactly one dummy row in each solution.
+    }
+    for dummy_letter in b'a'..=b'z' {
+        if skippable & (1 << (dummy_letter - b'a')) == 0 {
+            continue;
+        }
+        let mut dummy_row: Vec<bool> = (b'a'..=b'z').map(|b| b == dummy_letter).collect();
+        dummy_row.push(true);
+        rows.push(dummy_row);
+    }
+    rows
+}
+
+/// Every set of five `words` that uses each letter within --letter-bounds, as sorted words.
+/// Unlike the other searches this one has a row per word instead of per anagram class: bounds
+/// that let letters repeat also let two words of the same class into one solution. Repeated
+/// words in the list count once.
+pub fn dlx_bounded_words(words: &[String], options: &Options) -> Vec<Vec<String>> {
+    let bounds = options
+        .letter_bounds
+        .expect("dlx_bounded_words needs --letter-bounds");
+    assert!(
+        options.dlx_backend == DlxBackend::Soa
+            && options.resume.is_none()
+            && options.shard.is_none()
+            && options.sample.is_none(),
+        "--letter-bounds can't be combined with --dlx-backend aos, --resume, --shard or --sample"
+    );
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return Vec::new();
+    }
+    let (_reprs, keys) = build_word_representations(words);
+    let candidates = constraints.candidate_keys(&keys);
+    let mut words: Vec<&str> = words
+        .iter()
+        .map(|word| word.as_str())
+        .filter(|word| candidates.binary_search(&get_repr(word)).is_ok())
+        .collect();
+    words.sort();
+    words.dedup();
+    let word_keys: Vec<u32> = words.iter().map(|word| get_repr(word)).collect();
+    dlx_bounded_solutions(&word_keys, &bounds, &constraints)
+        .into_iter()
+        .map(|solution| solution.into_iter().map(|y| words[y].to_string()).collect())
+        .collect()
+}
+
+/// Five words with each letter used within its bounds, as exact cover with multiplicities. A
+/// 27th column, hit by every word, takes exactly five rows. A letter whose max is at most one more
+/// than its min is filled up to its max, as in dlx_solutions: its column takes exactly max rows,
+/// and it has a dummy row for the use that may be missing. Wider ranges are left to the column's
+/// own (min, max). A 28th column, hit by every dummy, takes as many dummies as the five words can
+/// leave over, which is exactly one for the default bounds and keeps the search as tight as the
+/// plain one. With at most one dummy per letter, each set of words is found exactly once.
+///
+/// Required letters raise their minimum to 1 and the unused letter gets a maximum of 0. Each
+/// included key gets one more column, hit only by the rows of its words, that takes exactly one
+/// row. `keys` has the key of each word, and solutions are given as positions in it.
+fn dlx_bounded_solutions(
+    keys: &[u32],
+    bounds: &LetterBounds,
+    constraints: &Constraints,
+) -> Vec<Vec<usize>> {
+    let mut bounds = *bounds;
+    for (letter, (min, max)) in bounds.iter_mut().enumerate() {
+        if constraints.letters & (1 << letter) != 0 {
+            *min = (*min).max(1);
+        }
+        if constraints.banned & (1 << letter) != 0 {
+            *max = 0;
+        }
+        if min > max {
+            return Vec::new();
+        }
+    }
+    let capacity = bounds.iter().map(|(_, max)| *max as usize).sum::<usize>();
+    if capacity < 25 {
+        return Vec::new();
+    }
+    let mut rows: Vec<Vec<u32>> = keys
+        .iter()
+        .map(|w| {
+            (0..26)
+                .filter(|b| (w & (1 << b)) != 0)
+                .chain([26])
+                .collect()
+        })
+        .collect();
+    for (i, required) in constraints.required.iter().enumerate() {
+        for (row, key) in rows.iter_mut().zip(keys) {
+            if key == required {
+                row.push(28 + i as u32);
+            }
+        }
+    }
+    let mut column_bounds: Vec<(u16, u16)> = bounds.to_vec();
+    // Uses of the letters filled up to their max, and the range of uses of the others
+    let (mut filled, mut loose_min, mut loose_max) = (0, 0, 0);
+    for (letter, (min, max)) in bounds.iter().enumerate() {
+        if *max > *min + 1 {
+            loose_min += min;
+            loose_max += max;
+            continue;
+        }
+        filled += max;
+        if *max == *min + 1 {
+            rows.push(vec![letter as u32, 27]);
+            column_bounds[letter] = (*max, *max);
+        }
+    }
+    column_bounds.push((5, 5));
+    column_bounds.push((
+        (filled + loose_min).saturating_sub(25),
+        (filled + loose_max).saturating_sub(25),
+    ));
+    column_bounds.extend(constraints.required.iter().map(|_| (1, 1)));
+
+    dancing_links_soa::DLMatrix::from_multiplicity_rows(&column_bounds, &rows)
+        .exact_cover_multiplicity()
+        .into_iter()
+        .map(|solution| {
+            let mut words: Vec<usize> = solution
+                .into_iter()
+                .map(|y| y as usize)
+                .filter(|y| *y < keys.len())
+                .collect();
+            words.sort();
+            words
+        })
+        .collect()
+}
+
+#[cfg(test)]
+macro_rules! exact_cover_tests {
+    ($backend:ident, $matrix:ty) => {
+        mod $backend {
+            use super::*;
+
+            #[test]
+            fn test_create_dlmatrix() {
+                let columns = vec![
+                    vec![true, false, true],
+                    vec![false, true, false],
+                    vec![true, true, true],
+                ];
+                let m = <$matrix>::from_bool_columns(&columns);
+                dbg!(m);
+                // TODO: add a way to traverse the matrix
+            }
+
+            #[test]
+            fn test_simple_exact_cover() {
+                let columns = vec![
+                    vec![true, false, true],
+                    vec![true, true, false],
+                    vec![false, true, false],
+                ];
+                let mut m = <$matrix>::from_bool_columns(&columns);
+                dbg!(&m);
+                let solutions = m.solutions();
+                println!("Solutions size: {}", solutions.len());
+                assert_eq!(solutions.len(), 1);
+                for (i, solution) in solutions.iter().enumerate() {
+                    println!("Solution {}", i);
+                    dbg!(&solution);
+                    println!();
+                }
+            }
+
+            #[test]
+            fn test_simple_exact_cover_3el_col() {
+                let columns = vec![
+                    vec![true, true, true],
+                    vec![false, true, false],
+                    vec![false, true, true],
+                ];
+                let mut m = <$matrix>::from_bool_columns(&columns);
+                dbg!(&m);
+                let solutions = m.solutions();
+                println!("Solutions size: {}", solutions.len());
+                assert_eq!(solutions.len(), 1);
+                for (i, solution) in solutions.iter().enumerate() {
+                    println!("Solution {}", i);
+                    dbg!(&solution);
+                    println!();
+                }
+            }
+
+            #[test]
+            fn test_simple_exact_cover_multi_solution() {
+                let columns = vec![
+                    vec![true, false, true],
+                    vec![true, false, true],
+                    vec![false, true, true],
+                ];
+                let mut m = <$matrix>::from_bool_columns(&columns);
+                dbg!(&m);
+                let solutions = m.solutions();
+                println!("Solutions size: {}", solutions.len());
+                assert_eq!(solutions.len(), 2);
+                for (i, solution) in solutions.iter().enumerate() {
+                    println!("Solution {}", i);
+                    dbg!(&solution);
+                    println!();
+                }
+            }
+
+            #[test]
+            fn test_medium_exact_cover() {
+                let columns = vec![
+                    vec![false, true, false, true, false, false],
+                    vec![false, false, true, false, true, false],
+                    vec![true, false, true, false, false, false],
+                    vec![false, true, false, true, false, true],
+                    vec![true, false, false, false, false, true],
+                    vec![true, false, true, false, false, false],
+                    vec![false, true, false, false, false, true],
+                ];
+                let mut m = <$matrix>::from_bool_columns(&columns);
+
+                dbg!(&m);
+                let solutions = m.solutions();
+                println!("Solutions size: {}", solutions.len());
+                assert_eq!(solutions.len(), 1);
+                for (i, solution) in solutions.iter().enumerate() {
+                    println!("Solution {}", i);
+                    for row in solution.iter() {
+                        print!("{}", *row);
+                    }
+                    println!();
+                }
+            }
+
+            #[test]
+            fn test_weird_exact_cover() {
+                let columns = vec![
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![true],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![true],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![true],
+                    vec![false],
+                    vec![true],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![false],
+                    vec![true],
+                ];
+                let mut m = <$matrix>::from_bool_columns(&columns);
+                dbg!(&m);
+                m.print();
+                let solutions = m.solutions();
+                println!("Solutions size: {}", solutions.len());
+            }
+
+            #[test]
+            fn test_dlx_words_match_brute() {
+                use crate::word_reprs::*;
+                let words = test_words();
+                let (_reprs, keys) = build_word_representations(&words);
+                let options = Options {
+                    dlx_backend: DlxBackend::from_name(stringify!($backend)).unwrap(),
+                    ..Options::default()
+                };
+                let expected =
+                    collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+                assert!(!expected.is_empty());
+                assert_eq!(collect_sorted_with(dlx_keys, &keys, &options), expected);
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+exact_cover_tests!(aos, dancing_links::DLMatrix);
+#[cfg(test)]
+exact_cover_tests!(soa, dancing_links_soa::DLMatrix);
+
+#[test]
+fn test_dlx_heuristics_match_brute() {
+    use crate::word_reprs::*;
+    // Only the SoA matrix has heuristics, the AoS one always uses MRV
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let expected = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    for heuristic in ColumnHeuristic::ALL {
+        let options = Options {
+            dlx_backend: DlxBackend::Soa,
+            dlx_heuristic: heuristic,
+            seed: Some(7),
+            ..Options::default()
+        };
+        assert_eq!(
+            collect_sorted_with(dlx_keys, &keys, &options),
+            expected,
+            "{:?}",
+            heuristic
+        );
+    }
+}
+
+#[cfg(test)]
+const SQUARE: usize = 5;
+
+/// Every 5x5 double word square (five words across and five down, all different), as an
+/// example of the colored exact cover: crossword-style slot fill, where the 10 slots (5 across,
+/// then 5 down) are primary columns, the 25 cells are secondary columns colored by their letter,
+/// and every word has an uncolored secondary column so it's used at most once. Row slot * n + w
+/// puts word w into the slot. Returns the across words of each square, top to bottom.
+#[cfg(test)]
+fn word_squares(words: &[String]) -> Vec<Vec<String>> {
+    let slots = 2 * SQUARE;
+    let first_word_column = slots + SQUARE * SQUARE;
+    let mut rows = Vec::with_capacity(slots * words.len());
+    for slot in 0..slots {
+        for (word_no, word) in words.iter().enumerate() {
+            let mut row = vec![(slot as u32, 0)];
+            for (i, letter) in word.bytes().enumerate() {
+                let cell = if slot < SQUARE {
+                    slot * SQUARE + i
+                } else {
+                    i * SQUARE + slot - SQUARE
+                };
+                row.push(((slots + cell) as u32, (letter - b'a' + 1) as i32));
+            }
+            row.push(((first_word_column + word_no) as u32, 0));
+            rows.push(row);
+        }
+    }
+
+    let mut matrix = dancing_links_soa::DLMatrix::from_colored_rows(slots as u32, &rows);
+    matrix
+        .exact_cover_colored()
+        .into_iter()
+        .map(|solution| {
+            let mut across: Vec<(usize, &String)> = solution
+                .iter()
+                .map(|y| (*y as usize / words.len(), &words[*y as usize % words.len()]))
+                .filter(|(slot, _)| *slot < SQUARE)
+                .collect();
+            across.sort();
+            across.into_iter().map(|(_, word)| word.clone()).collect()
+        })
+        .collect()
+}
+
+#[test]
+fn test_word_squares() {
+    let across = ["abcde", "fghij", "klmno", "pqrst", "uvwxy"];
+    let down = ["afkpu", "bglqv", "chmrw", "dinsx", "ejoty"];
+    let mut words: Vec<String> = across
+        .iter()
+        .chain(down.iter())
+        .map(|w| w.to_string())
+        .collect();
+    words.push(String::from("abcdz")); // Fits the first row but no column starting with z
+    let mut squares = word_squares(&words);
+    squares.sort();
+    assert_eq!(squares, vec![across, down]);
+}
+
+#[test]
+fn test_word_squares_large_list() {
+    // 10 slots x 1010 words of 7 nodes each is past the 65535 nodes DLMatrix used to allow. The
+    // filler words start with z and have no z anywhere else, so they only fit in the first row or
+    // column, and then only if the words crossing them start with their other letters; that ends
+    // most branches right away.
+    let mut rng = crate::rng::SplitMix64::new(5);
+    let mut words: Vec<String> = (0..1000)
+        .map(|_| {
+            let rest: String = (1..SQUARE)
+                .map(|_| (b'a' + rng.below(25) as u8) as char)
+                .collect();
+            format!("z{}", rest)
+        })
+        .collect();
+    let across = ["abcde", "fghij", "klmno", "pqrst", "uvwxy"];
+    let down = ["afkpu", "bglqv", "chmrw", "dinsx", "ejoty"];
+    words.extend(across.iter().chain(down.iter()).map(|w| w.to_string()));
+    let mut squares = word_squares(&words);
+    squares.sort();
+    assert_eq!(squares, vec![across, down]);
+}
+
+#[test]
+fn test_letter_bounds() {
+    use crate::word_reprs::*;
+    use itertools::Itertools;
+
+    let sorted = |solutions: Vec<Vec<String>>| -> Vec<String> {
+        solutions
+            .iter()
+            .map(|words| words.join(" "))
+            .sorted()
+            .collect()
+    };
+
+    // The defaults are the plain problem
+    let words = test_words();
+    let options = Options {
+        letter_bounds: parse_letter_bounds("a=0..1"),
+        ..Options::default()
+    };
+    let expected = crate::verify::solver_solutions(
+        &words,
+        crate::backtracking_brute::backtracking_brute_keys,
+        &Options::default(),
+    );
+    assert_eq!(
+        sorted(dlx_bounded_words(&words, &options)),
+        expected.into_iter().sorted().collect::<Vec<_>>()
+    );
+
+    // angle and glean are one anagram class, and can both be used once their letters may repeat
+    let mut words = test_words();
+    words.extend(["angle", "glean", "fjord", "whips", "bucky"].map(String::from));
+    let bounds = parse_letter_bounds("aeglnu=0..2,xz=0..0").unwrap();
+    let options = Options {
+        letter_bounds: Some(bounds),
+        ..Options::default()
+    };
+    let unique: Vec<&String> = words.iter().unique().collect();
+    let naive: Vec<String> = unique
+        .iter()
+        .combinations(5)
+        .filter(|combo| {
+            bounds.iter().enumerate().all(|(letter, (min, max))| {
+                let used = combo
+                    .iter()
+                    .filter(|word| get_repr(word) & (1 << letter) != 0)
+                    .count();
+                (*min as usize..=*max as usize).contains(&used)
+            })
+        })
+        .map(|combo| combo.iter().sorted().join(" "))
+        .sorted()
+        .collect();
+    assert!(naive.contains(&"angle bucky fjord glean whips".to_string()));
+    assert_eq!(sorted(dlx_bounded_words(&words, &options)), naive);
+}
+
+#[test]
+fn test_parse_letter_bounds() {
+    let bounds = parse_letter_bounds("aeiou=0..2,e=1..1").unwrap();
+    assert_eq!(bounds[0], (0, 2));
+    assert_eq!(bounds[4], (1, 1));
+    assert_eq!(bounds[1], (0, 1));
+    assert_eq!(parse_letter_bounds("a=2..1"), None);
+    assert_eq!(parse_letter_bounds("A=0..1"), None);
+    assert_eq!(parse_letter_bounds("a"), None);
+}
+
+#[test]
+fn test_dlx_sample() {
+    use crate::word_reprs::*;
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let all = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    assert!(all.len() > 2);
+
+    for sampler in [Sampler::Shuffle, Sampler::Uniform] {
+        let options = Options {
+            sample: Some(2),
+            sampler,
+            seed: Some(42),
+            ..Options::default()
+        };
+        let sample = collect_sorted_with(dlx_keys, &keys, &options);
+        assert_eq!(sample.len(), 2);
+        assert_ne!(sample[0], sample[1]);
+        assert!(sample.iter().all(|combo| all.contains(combo)));
+        assert_eq!(collect_sorted_with(dlx_keys, &keys, &options), sample);
+    }
+
+    // Asking for more than there are gives all of them
+    let options = Options {
+        sample: Some(all.len() + 10),
+        ..Options::default()
+    };
+    assert_eq!(collect_sorted_with(dlx_keys, &keys, &options), all);
+}
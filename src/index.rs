@@ -0,0 +1,396 @@
+// Preprocessed word list, written by the index mode and read back instead of the text file.
+//
+// The file is little endian and laid out so every section can be used in place (and mapped
+// straight into memory): a fixed header, then u32-aligned sections of
+//
+//   source path    u32 length + bytes, padded to 4; absolute
+//   keys           key_count u32s, sorted
+//   group starts   key_count + 1 u32s; the words of keys[i] are words[starts[i]..starts[i + 1]]
+//   words          word_count × 5 ASCII letters, grouped by key, padded to 4
+//   pairs          pair_count × (combo_key, pos1, pos2) u32s, if FLAG_PAIRS is set
+//
+// The header holds a checksum of everything after it, to catch truncated or corrupted files, and
+// one of the text file the index was built from, to catch indexes that are older than it.
+// A loaded `Index` borrows the file's bytes: the keys, group starts and pairs are used in place
+// on little endian machines (when the bytes are 4-aligned, as a read buffer is) and only copied
+// otherwise, and the words are never split into separate strings.
+// Filters on the text file (tags, --include, ...) are applied when the index is written. Tags and
+// frequencies are not kept, so tag filters, --format json and rank without --freq-file need the
+// text file.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::find_solver;
+use crate::options::Options;
+use crate::smart_brute::{pair_table, smart_brute_with_pairs};
+use crate::word_reprs::*;
+
+const MAGIC: &[u8; 4] = b"FLWI";
+const VERSION: u32 = 1;
+const HEADER_BYTES: usize = 40;
+const FLAG_PAIRS: u32 = 1;
+
+#[derive(Debug, PartialEq)]
+pub struct Index<'a> {
+    pub source: Cow<'a, str>,
+    /// The words back to back, 5 letters each, grouped by key
+    words: Cow<'a, str>,
+    pub keys: Cow<'a, [u32]>,
+    pub group_starts: Cow<'a, [u32]>,
+    pub pairs: Option<Cow<'a, [[u32; 3]]>>,
+}
+
+impl Index<'_> {
+    pub fn build(words: &[String], source: &Path, with_pairs: bool) -> Index<'static> {
+        let (reprs, keys) = build_word_representations(words);
+        let mut group_starts = vec![0u32];
+        let mut grouped = String::with_capacity(words.len() * 5);
+        for key in keys.iter() {
+            for word in reprs[key].iter() {
+                if word.len() != 5 || !word.bytes().all(|b| b.is_ascii_lowercase()) {
+                    panic!("can't index {:?}: words must be 5 letters a-z", word);
+                }
+                grouped.push_str(word);
+            }
+            group_starts.push((grouped.len() / 5) as u32);
+        }
+        Index {
+            source: Cow::Owned(source.display().to_string()),
+            words: Cow::Owned(grouped),
+            pairs: with_pairs.then(|| Cow::Owned(pair_table(&keys))),
+            keys: Cow::Owned(keys),
+            group_starts: Cow::Owned(group_starts),
+        }
+    }
+
+    pub fn word_count(&self) -> usize {
+        self.words.len() / 5
+    }
+
+    /// The words, grouped by key
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        (0..self.word_count()).map(|i| &self.words[i * 5..(i + 1) * 5])
+    }
+
+    /// Words of each key, as `build_word_representations` returns them
+    pub fn reprs(&self) -> HashMap<u32, Vec<&str>> {
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                let group = self.group_starts[i] as usize..self.group_starts[i + 1] as usize;
+                (
+                    *key,
+                    group.map(|w| &self.words[w * 5..(w + 1) * 5]).collect(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn to_bytes(&self, source_checksum: u64) -> Vec<u8> {
+        let mut payload = Vec::new();
+        push_u32(&mut payload, self.source.len() as u32);
+        payload.extend_from_slice(self.source.as_bytes());
+        pad(&mut payload);
+        self.keys
+            .iter()
+            .for_each(|key| push_u32(&mut payload, *key));
+        self.group_starts
+            .iter()
+            .for_each(|start| push_u32(&mut payload, *start));
+        payload.extend_from_slice(self.words.as_bytes());
+        pad(&mut payload);
+        for pair in self.pairs.iter().flat_map(|pairs| pairs.iter()) {
+            pair.iter().for_each(|value| push_u32(&mut payload, *value));
+        }
+
+        let mut bytes = Vec::with_capacity(HEADER_BYTES + payload.len());
+        bytes.extend_from_slice(MAGIC);
+        push_u32(&mut bytes, VERSION);
+        bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+        bytes.extend_from_slice(&source_checksum.to_le_bytes());
+        push_u32(&mut bytes, self.word_count() as u32);
+        push_u32(&mut bytes, self.keys.len() as u32);
+        push_u32(
+            &mut bytes,
+            self.pairs.as_ref().map_or(0, |pairs| pairs.len()) as u32,
+        );
+        push_u32(
+            &mut bytes,
+            if self.pairs.is_some() { FLAG_PAIRS } else { 0 },
+        );
+        bytes.extend_from_slice(&payload);
+        bytes
+    }
+
+    /// Parses an index, returning it with the checksum of its source file.
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Index<'_>, u64), String> {
+        if !is_index(bytes) {
+            return Err("not an index file".to_string());
+        }
+        if bytes.len() < HEADER_BYTES {
+            return Err("truncated header".to_string());
+        }
+        let mut header = Reader {
+            bytes: &bytes[..HEADER_BYTES],
+            pos: 4,
+        };
+        let version = header.u32()?;
+        if version != VERSION {
+            return Err(format!("index version {}, expected {}", version, VERSION));
+        }
+        let payload_checksum = header.u64()?;
+        let source_checksum = header.u64()?;
+        let payload = &bytes[HEADER_BYTES..];
+        if checksum(payload) != payload_checksum {
+            return Err("checksum mismatch, the index is corrupted".to_string());
+        }
+        let word_count = header.u32()? as usize;
+        let key_count = header.u32()? as usize;
+        let pair_count = header.u32()? as usize;
+        let flags = header.u32()?;
+
+        let mut reader = Reader {
+            bytes: payload,
+            pos: 0,
+        };
+        let source_len = reader.u32()? as usize;
+        let source = String::from_utf8_lossy(reader.take(source_len)?);
+        reader.align();
+        let keys = reader.u32s(key_count)?;
+        let group_starts = reader.u32s(key_count + 1)?;
+        let words = reader.take(word_count * 5)?;
+        if !words.is_ascii() {
+            return Err("words must be ASCII".to_string());
+        }
+        let words = Cow::Borrowed(std::str::from_utf8(words).unwrap());
+        reader.align();
+        let pairs = if flags & FLAG_PAIRS != 0 {
+            Some(match reader.u32s(pair_count * 3)? {
+                Cow::Borrowed(values) => Cow::Borrowed(values.as_chunks().0),
+                Cow::Owned(values) => {
+                    Cow::Owned(values.chunks(3).map(|v| [v[0], v[1], v[2]]).collect())
+                }
+            })
+        } else {
+            None
+        };
+        let index = Index {
+            source,
+            words,
+            keys,
+            group_starts,
+            pairs,
+        };
+        Ok((index, source_checksum))
+    }
+
+    /// Loads the index read from `path`, panicking if it is broken or its source file has changed
+    /// since. A source file that can't be read is only warned about.
+    pub fn load<'a>(path: &Path, bytes: &'a [u8]) -> Index<'a> {
+        match Index::try_load(path, bytes) {
+            Err(why) => panic!("{}", why),
+            Ok(index) => index,
+        }
+    }
+
+    /// Same as `load`, returning what is wrong with the index instead of panicking.
+    pub fn try_load<'a>(path: &Path, bytes: &'a [u8]) -> Result<Index<'a>, String> {
+        let (index, source_checksum) = Index::from_bytes(bytes)
+            .map_err(|why| format!("couldn't load index {}: {}", path.display(), why))?;
+        match fs::read(index.source.as_ref()) {
+            Err(why) => eprintln!(
+                "warning: can't tell whether index {} is stale, couldn't read {}: {}",
+                path.display(),
+                index.source,
+                why
+            ),
+            Ok(source) if checksum(&source) != source_checksum => {
+                return Err(format!(
+                    "index {} is stale, {} changed since; rebuild it with the index mode",
+                    path.display(),
+                    index.source
+                ))
+            }
+            Ok(_) => (),
+        }
+        Ok(index)
+    }
+}
+
+pub fn is_index(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// FNV-1a, 64 bit
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+fn push_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn pad(bytes: &mut Vec<u8>) {
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self.pos + len;
+        if end > self.bytes.len() {
+            return Err("truncated index".to_string());
+        }
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn align(&mut self) {
+        self.pos = self.pos.next_multiple_of(4);
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// `count` u32s, in place when the machine is little endian and they are 4-aligned
+    fn u32s(&mut self, count: usize) -> Result<Cow<'a, [u32]>, String> {
+        let bytes = self.take(count * 4)?;
+        if cfg!(target_endian = "little") {
+            // SAFETY: every bit pattern is a valid u32, and align_to only puts aligned, whole
+            // u32s in the middle
+            let (head, values, _) = unsafe { bytes.align_to::<u32>() };
+            if head.is_empty() && values.len() == count {
+                return Ok(Cow::Borrowed(values));
+            }
+        }
+        Ok(Cow::Owned(
+            bytes
+                .chunks(4)
+                .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+                .collect(),
+        ))
+    }
+}
+
+/// `index <out> [pairs]`: writes the index of `words`, read from `source`.
+pub fn write_index(words: &[String], source: &Path, args: &[String]) {
+    let out = match args.first() {
+        Some(out) => Path::new(out),
+        None => panic!("usage: <wordfile> index <out> [pairs]"),
+    };
+    let with_pairs = args.get(1).map(|arg| arg.as_str()) == Some("pairs");
+    let source_bytes = match fs::read(source) {
+        Err(why) => panic!("couldn't read {}: {}", source.display(), why),
+        Ok(bytes) => bytes,
+    };
+    // Absolute, so that the staleness check finds the source from any directory
+    let source = match fs::canonicalize(source) {
+        Err(why) => panic!("couldn't resolve {}: {}", source.display(), why),
+        Ok(source) => source,
+    };
+    let index = Index::build(words, &source, with_pairs);
+    let bytes = index.to_bytes(checksum(&source_bytes));
+    if let Err(why) = fs::write(out, &bytes) {
+        panic!("couldn't write {}: {}", out.display(), why);
+    }
+    eprintln!(
+        "index: {} words, {} keys, {} pairs, {:.2} MiB",
+        index.word_count(),
+        index.keys.len(),
+        index.pairs.as_ref().map_or(0, |pairs| pairs.len()),
+        bytes.len() as f64 / (1024.0 * 1024.0)
+    );
+}
+
+/// Runs a solver on the index's keys, using its pair table where the solver has a use for one.
+pub fn solve_indexed(index: &Index<'_>, name: &str, options: &Options) {
+    let reprs = index.reprs();
+    let emit = |combo| print_combo(combo, &reprs);
+    match (name, &index.pairs) {
+        ("smart_brute_par", Some(pairs)) => {
+            smart_brute_with_pairs(&index.keys, pairs, options, &emit)
+        }
+        _ => match find_solver(name) {
+            Some(solver) => solver(&index.keys, options, &emit),
+            None => panic!("unknown solver {}", name),
+        },
+    }
+}
+
+#[test]
+fn test_index_round_trip() {
+    use crate::smart_brute::smart_brute_keys;
+
+    let words = test_words();
+    let index = Index::build(&words, Path::new("words.txt"), true);
+    let bytes = index.to_bytes(42);
+    assert_eq!(bytes.len() % 4, 0);
+    let (loaded, source_checksum) = Index::from_bytes(&bytes).unwrap();
+    assert_eq!(loaded, index);
+    assert_eq!(source_checksum, 42);
+    // A Vec's buffer is aligned well beyond 4 bytes
+    assert!(matches!(loaded.keys, Cow::Borrowed(_)));
+    assert!(matches!(loaded.pairs, Some(Cow::Borrowed(_))));
+
+    let (reprs, keys) = build_word_representations(&words);
+    assert_eq!(loaded.keys, keys);
+    let mut loaded_reprs = loaded.reprs();
+    loaded_reprs.values_mut().for_each(|words| words.sort());
+    let mut reprs = reprs;
+    reprs.values_mut().for_each(|words| words.sort());
+    assert_eq!(loaded_reprs, reprs);
+
+    let pairs = loaded.pairs.as_ref().unwrap();
+    let found = std::sync::Mutex::new(Vec::new());
+    smart_brute_with_pairs(&keys, pairs, &Options::default(), &|mut combo| {
+        combo.sort();
+        found.lock().unwrap().push(combo);
+    });
+    let mut found = found.into_inner().unwrap();
+    found.sort();
+    assert_eq!(found, collect_sorted(smart_brute_keys, &keys));
+
+    let mut corrupted = bytes.clone();
+    *corrupted.last_mut().unwrap() ^= 1;
+    assert!(Index::from_bytes(&corrupted).is_err());
+    let mut old = bytes;
+    old[4] = 0;
+    assert!(Index::from_bytes(&old).unwrap_err().contains("version"));
+}
+
+#[test]
+fn test_index_keeps_absolute_source() {
+    // Relative to the crate root, where the tests run
+    let source = format!("target/flw-index-source-{}.txt", std::process::id());
+    let source = Path::new(&source);
+    let out = std::env::temp_dir().join(format!("flw-index-{}.idx", std::process::id()));
+    fs::write(source, test_words().join("\n")).unwrap();
+    write_index(&test_words(), source, &[out.display().to_string()]);
+
+    let bytes = fs::read(&out).unwrap();
+    let index = Index::try_load(&out, &bytes).unwrap();
+    assert_eq!(
+        Path::new(index.source.as_ref()),
+        fs::canonicalize(source).unwrap()
+    );
+    fs::write(source, "fjord\n").unwrap();
+    assert!(Index::try_load(&out, &bytes).unwrap_err().contains("stale"));
+    let _ = fs::remove_file(source);
+    let _ = fs::remove_file(out);
+}
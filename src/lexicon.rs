@@ -0,0 +1,195 @@
+// Word list lines and what they carry besides the word.
+//
+// A line is the word followed by optional whitespace-separated columns: a column that parses as
+// a finite number >= 0 is the word's frequency (see ranking), any other column is a
+// comma-separated list of tags, as in `fjord<TAB>noun,geo`, so a tag like `inf` stays a tag.
+// Tags can be filtered on with --allow-tags / --deny-tags and are printed with each word by
+// --format json.
+
+use std::collections::HashMap;
+use std::io::prelude::*;
+
+use itertools::Itertools;
+
+use crate::options::Options;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entry {
+    pub word: String,
+    pub frequency: Option<f64>,
+    pub tags: Vec<String>,
+}
+
+pub fn parse_line(line: &str) -> Entry {
+    let mut fields = line.split_whitespace();
+    let mut entry = Entry {
+        word: fields.next().unwrap_or("").to_string(),
+        ..Entry::default()
+    };
+    for field in fields {
+        match field.parse::<f64>() {
+            Ok(frequency)
+                if frequency.is_finite() && frequency >= 0.0 && entry.frequency.is_none() =>
+            {
+                entry.frequency = Some(frequency)
+            }
+            _ => entry.tags.extend(
+                field
+                    .split(',')
+                    .filter(|tag| !tag.is_empty())
+                    .map(|tag| tag.to_string()),
+            ),
+        }
+    }
+    entry
+}
+
+/// Keeps the entries with at least one allowed tag (if any are given) and no denied one.
+pub fn filter_tags(entries: Vec<Entry>, options: &Options) -> Vec<Entry> {
+    entries
+        .into_iter()
+        .filter(|entry| {
+            options.allow_tags.is_empty()
+                || entry
+                    .tags
+                    .iter()
+                    .any(|tag| options.allow_tags.contains(tag))
+        })
+        .filter(|entry| !entry.tags.iter().any(|tag| options.deny_tags.contains(tag)))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// One solution per line, words separated by spaces
+    #[default]
+    Text,
+    /// One JSON object per line, with each word's tags
+    Json,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(OutputFormat::Text),
+            "json" => Some(OutputFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Tags of each word, for structured output.
+#[derive(Debug, Default)]
+pub struct Tags(HashMap<String, Vec<String>>);
+
+impl Tags {
+    pub fn new(entries: &[Entry]) -> Self {
+        Tags(
+            entries
+                .iter()
+                .map(|entry| (entry.word.clone(), entry.tags.clone()))
+                .collect(),
+        )
+    }
+
+    pub fn get(&self, word: &str) -> &[String] {
+        self.0.get(word).map(|tags| tags.as_slice()).unwrap_or(&[])
+    }
+
+    /// `[{"word": ..., "tags": [...]}, ...]`
+    pub fn json_words(&self, words: &[&str]) -> String {
+        let mut objects = words.iter().map(|word| {
+            let tags = self.get(word).iter().map(|tag| json_string(tag)).join(",");
+            format!("{{\"word\":{},\"tags\":[{}]}}", json_string(word), tags)
+        });
+        format!("[{}]", objects.join(","))
+    }
+
+    /// Prints every word-level solution a combo stands for as a JSON line.
+    pub fn print_combo_json(&self, combo: [u32; 5], reprs: &HashMap<u32, Vec<&str>>) {
+        for word_combo in combo
+            .iter()
+            .map(|key| &reprs[key])
+            .multi_cartesian_product()
+        {
+            let words: Vec<&str> = word_combo.into_iter().copied().collect();
+            self.print_words_json(&words);
+        }
+    }
+
+    /// Prints one solution as a JSON line
+    pub fn print_words_json(&self, words: &[&str]) {
+        let line = format!("{{\"words\":{}}}", self.json_words(words));
+        let _ = writeln!(std::io::stdout(), "{}", line);
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => out.push(ch),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[test]
+fn test_parse_line() {
+    assert_eq!(parse_line("fjord").word, "fjord");
+    let entry = parse_line("fjord\t1234.5");
+    assert_eq!((entry.frequency, entry.tags.len()), (Some(1234.5), 0));
+    let entry = parse_line("fjord\tnoun,geo\t12");
+    assert_eq!(entry.word, "fjord");
+    assert_eq!(entry.frequency, Some(12.0));
+    assert_eq!(entry.tags, vec!["noun", "geo"]);
+    // Not finite or negative, so tags rather than frequencies
+    let entry = parse_line("fjord\tinf\tnan\t-2\t7");
+    assert_eq!(entry.frequency, Some(7.0));
+    assert_eq!(entry.tags, vec!["inf", "nan", "-2"]);
+}
+
+#[test]
+fn test_filter_tags() {
+    let entries: Vec<Entry> = [
+        "fjord\tnoun",
+        "gucks\tnoun,slang",
+        "vozhd\tnoun,foreign",
+        "waltz",
+    ]
+    .iter()
+    .map(|line| parse_line(line))
+    .collect();
+    let words = |options: &Options| -> Vec<String> {
+        filter_tags(entries.clone(), options)
+            .into_iter()
+            .map(|entry| entry.word)
+            .collect()
+    };
+    assert_eq!(words(&Options::default()).len(), 4);
+    let options = Options {
+        deny_tags: vec!["slang".to_string(), "foreign".to_string()],
+        ..Options::default()
+    };
+    assert_eq!(words(&options), vec!["fjord", "waltz"]);
+    let options = Options {
+        allow_tags: vec!["noun".to_string()],
+        deny_tags: vec!["foreign".to_string()],
+        ..Options::default()
+    };
+    assert_eq!(words(&options), vec!["fjord", "gucks"]);
+
+    let tags = Tags::new(&entries);
+    assert_eq!(
+        tags.json_words(&["gucks", "waltz"]),
+        r#"[{"word":"gucks","tags":["noun","slang"]},{"word":"waltz","tags":[]}]"#
+    );
+    assert_eq!(json_string("a\"b\\"), r#""a\"b\\""#);
+}
@@ -1,59 +1,246 @@
 extern crate prettytable;
 
 mod backtracking_brute;
-// mod dancing_links;
+mod bench;
+mod brute_rare_first;
+mod checkpoint;
+mod clique;
+mod cnf;
+mod compat_index;
+mod constraints;
+mod dancing_links;
 mod dancing_links_soa;
+mod dlx_format;
+mod estimate;
+mod exact_cover;
+mod index;
+mod lexicon;
+mod options;
+mod partial_cover;
+mod ranking;
+mod repl;
+mod rng;
+mod serve;
+mod shard;
+mod simd_filter;
 mod smart_brute;
+mod verify;
 mod word_reprs;
+mod wordle;
 
 use std::collections::HashSet;
 use std::env;
-use std::fs::File;
+use std::fs;
 use std::io::prelude::*;
-use std::io::BufReader;
 use std::path::Path;
 
-use crate::backtracking_brute::{backtracking_brute, backtracking_brute_parallelized};
-use crate::dancing_links_soa::dlx_words;
-use crate::smart_brute::smart_brute;
+use crate::backtracking_brute::*;
+use crate::bench::bench;
+use crate::brute_rare_first::*;
+use crate::checkpoint::RESUMABLE_SOLVERS;
+use crate::clique::{clique_keys, write_graph};
+use crate::cnf::{export_cnf, import_model};
+use crate::constraints::filter_words;
+use crate::dlx_format::{export_dlx, solve_dlx_file};
+use crate::estimate::estimate;
+use crate::exact_cover::*;
+use crate::index::{is_index, solve_indexed, write_index, Index};
+use crate::lexicon::{filter_tags, parse_line, Entry, OutputFormat, Tags};
+use crate::options::Options;
+use crate::partial_cover::cover;
+use crate::ranking::{rank, Frequencies};
+use crate::repl::repl;
+use crate::serve::serve;
+use crate::shard::{merge, SHARDED_SOLVERS};
+use crate::smart_brute::*;
+use crate::verify::verify;
+use crate::word_reprs::*;
+use crate::wordle::wordle;
+
+pub type SolverFn = fn(&[u32], &Options, &ComboSink);
+
+/// Solvers by CLI name, working on the sorted anagram-class keys.
+pub const SOLVERS: &[(&str, SolverFn)] = &[
+    ("brute", backtracking_brute_keys),
+    ("brute_par", backtracking_brute_parallelized_keys),
+    ("brute_simd", backtracking_brute_simd_keys),
+    ("brute_rare_first", brute_rare_first_keys),
+    ("smart_brute_par", smart_brute_keys),
+    ("smart_brute_simd", smart_brute_simd_keys),
+    ("smart_brute_bounded", smart_brute_bounded_keys),
+    ("dlx", dlx_keys),
+    ("clique", clique_keys),
+];
+
+pub fn find_solver(name: &str) -> Option<SolverFn> {
+    SOLVERS
+        .iter()
+        .find(|(solver_name, _)| *solver_name == name)
+        .map(|(_, solver)| *solver)
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let path = Path::new(&args[1]);
-    let file = match File::open(&path) {
+    let (options, args) = Options::parse(&env::args().skip(1).collect::<Vec<_>>());
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .unwrap();
+    }
+    let path = Path::new(&args[0]);
+    let bytes = match fs::read(path) {
         Err(why) => panic!("couldn't open {}: {}", path.display(), why),
-        Ok(file) => file,
+        Ok(bytes) => bytes,
     };
+    if path.extension().is_some_and(|ext| ext == "dlx") {
+        solve_dlx_file(path, &bytes);
+        return;
+    }
+    let index = is_index(&bytes).then(|| Index::load(path, &bytes));
+    let tag_options = !options.allow_tags.is_empty() || !options.deny_tags.is_empty();
+    if index.is_some() && (tag_options || options.format == OutputFormat::Json) {
+        panic!("an index keeps no tags, use the word list for --allow-tags, --deny-tags and --format json");
+    }
 
-    let words: Vec<_> = BufReader::new(file)
-        .lines()
-        .map(|l| unpack_word(l))
-        .filter(|w| is_unique_5_letter(w))
-        .collect();
+    let entries = match index {
+        Some(_) => Vec::new(),
+        None => read_entries(&bytes, &options),
+    };
+    // Solvers run on the index in place, without copying its words out. --include narrows
+    // anagram classes down, so then the index's groups no longer fit, and --letter-bounds
+    // searches the words themselves.
+    let solve_index = index.is_some()
+        && options.include.is_empty()
+        && options.exclude.is_empty()
+        && options.letter_bounds.is_none()
+        && args.get(1).is_some_and(|name| find_solver(name).is_some());
+    let words = match &index {
+        Some(_) if solve_index => Vec::new(),
+        Some(index) => index.words().map(String::from).collect(),
+        None => entries.iter().map(|entry| entry.word.clone()).collect(),
+    };
+    let words = filter_words(words, &options);
 
-    if args.len() > 2 {
-        match &args[2].as_str() {
-            &"brute" => backtracking_brute(words),
-            &"brute_par" => backtracking_brute_parallelized(words),
-            &"smart_brute_par" => smart_brute(words),
-            &"dlx" => dlx_words(words),
-            _ => dlx_words(words),
+    let mode = args.get(1).map_or("dlx", |mode| mode.as_str());
+    if options.shard.is_some() && !SHARDED_SOLVERS.contains(&mode) {
+        panic!(
+            "--shard works with {}, not {}",
+            SHARDED_SOLVERS.join(", "),
+            mode
+        );
+    }
+    if options.resume.is_some() && !RESUMABLE_SOLVERS.contains(&mode) {
+        panic!(
+            "--resume works with {}, not {}",
+            RESUMABLE_SOLVERS.join(", "),
+            mode
+        );
+    }
+    if options.letter_bounds.is_some() && mode != "dlx" {
+        panic!("--letter-bounds only works with dlx, not {}", mode);
+    }
+    if options.sample.is_some() && mode != "dlx" {
+        panic!("--sample only works with dlx, not {}", mode);
+    }
+
+    if args.len() > 1 {
+        match args[1].as_str() {
+            "dlx" if options.format == OutputFormat::Json && options.letter_bounds.is_some() => {
+                let tags = Tags::new(&entries);
+                for solution in dlx_bounded_words(&words, &options) {
+                    tags.print_words_json(
+                        &solution
+                            .iter()
+                            .map(|word| word.as_str())
+                            .collect::<Vec<_>>(),
+                    );
+                }
+            }
+            name if options.format == OutputFormat::Json && find_solver(name).is_some() => {
+                print_json_solutions(
+                    &words,
+                    find_solver(name).unwrap(),
+                    &Tags::new(&entries),
+                    &options,
+                )
+            }
+            name if solve_index => solve_indexed(index.as_ref().unwrap(), name, &options),
+            "index" => write_index(&words, path, &args[2..]),
+            "merge" => merge(&words, &args[2..]),
+            "graph" => write_graph(&words, &args[2..]),
+            "export-cnf" => export_cnf(&words, &args[2..], &options),
+            "import-model" => import_model(&words, &args[2..]),
+            "export-dlx" => export_dlx(&words, &args[2..], &options),
+            "verify" => verify(&words, &args[2..], &options),
+            "repl" => repl(words, &args[2..], &options),
+            "wordle" => wordle(words, &Tags::new(&entries), &args[2..], &options),
+            "cover" => cover(words, &Tags::new(&entries), &args[2..], &options),
+            "serve" => serve(words, Tags::new(&entries), &args[2..], &options),
+            "brute" => backtracking_brute(words, &options),
+            "brute_par" => backtracking_brute_parallelized(words, &options),
+            "brute_simd" => backtracking_brute_simd(words, &options),
+            "brute_rare_first" => brute_rare_first(words, &options),
+            "smart_brute_par" => smart_brute(words, &options),
+            "smart_brute_simd" => smart_brute_simd(words, &options),
+            "dlx" => dlx_words(words, &options),
+            "bench" => bench(words, &args[2..], &options),
+            "estimate" => estimate(words, &options),
+            "rank" => {
+                if is_index(&bytes) && options.freq_file.is_none() {
+                    panic!("an index keeps no word frequencies, rank it with --freq-file");
+                }
+                let frequencies = Frequencies::load(&entries, &options);
+                rank(words, &frequencies, &Tags::new(&entries), &options)
+            }
+            name => match find_solver(name) {
+                Some(solver) => print_solutions(&words, solver, &options),
+                None => dlx_words(words, &options),
+            },
         };
     } else {
-        dlx_words(words);
+        dlx_words(words, &options);
+    }
+    // merge reads the trailer off the text output; JSON lines have to stay JSON
+    if let Some(shard) = options
+        .shard
+        .filter(|_| options.format == OutputFormat::Text)
+    {
+        println!("{}", shard.trailer());
     }
 }
 
-fn unpack_word<T>(line: Result<String, T>) -> String {
-    if let Ok(line) = line {
-        line
-    } else {
-        String::from("")
+fn print_solutions(words: &[String], solver: SolverFn, options: &Options) {
+    let (reprs, keys) = build_word_representations(words);
+    solver(&keys, options, &|combo| print_combo(combo, &reprs));
+}
+
+fn print_json_solutions(words: &[String], solver: SolverFn, tags: &Tags, options: &Options) {
+    let (mut reprs, keys) = build_word_representations(words);
+    if options.sample.is_some() {
+        sampled_classes(&mut reprs);
     }
+    solver(&keys, options, &|combo| {
+        tags.print_combo_json(combo, &reprs)
+    });
+}
+
+/// The word list's entries that are 5 distinct letters, after --allow-tags / --deny-tags.
+pub fn read_entries(bytes: &[u8], options: &Options) -> Vec<Entry> {
+    let entries = bytes
+        .lines()
+        .map(unpack_word)
+        .map(|line| parse_line(&line))
+        .filter(|entry| is_unique_5_letter(&entry.word))
+        .collect();
+    filter_tags(entries, options)
+}
+
+fn unpack_word<T>(line: Result<String, T>) -> String {
+    line.unwrap_or_default()
 }
 
-fn is_unique_5_letter(word: &String) -> bool {
-    let mut s = word.clone();
+fn is_unique_5_letter(word: &str) -> bool {
+    let mut s = word.to_string();
     s.truncate(s.trim_end().len());
     if s.chars().count() != 5 {
         return false;
@@ -62,7 +249,7 @@ fn is_unique_5_letter(word: &String) -> bool {
     // TODO: consider using a different method to avoid the set allocation
     let mut chars: HashSet<char> = HashSet::new();
     s.chars().for_each(|ch| {
-        chars.insert(ch.clone());
+        chars.insert(ch);
     });
-    return chars.len() == 5;
+    chars.len() == 5
 }
@@ -0,0 +1,209 @@
+// Command line flags shared by the solvers.
+//
+// Flags are `--name value` pairs and may appear anywhere after the program name; everything
+// else is a positional argument (the word list path, the solver name, ...).
+
+use std::path::PathBuf;
+
+use crate::constraints::parse_letters;
+use crate::exact_cover::{parse_letter_bounds, ColumnHeuristic, DlxBackend, LetterBounds, Sampler};
+use crate::lexicon::OutputFormat;
+use crate::ranking::RankBy;
+use crate::shard::Shard;
+use crate::wordle::WordleScore;
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Memory budget for the pair tables of smart_brute_bounded, in bytes
+    pub mem_cap: Option<usize>,
+    /// Directory where smart_brute_bounded spills its pair table partitions
+    pub spill_dir: Option<PathBuf>,
+    /// Number of threads for the parallel solvers (rayon's default when unset)
+    pub threads: Option<usize>,
+    /// Dancing links implementation used by dlx
+    pub dlx_backend: DlxBackend,
+    /// Per-letter bounds for dlx (always solved with the SoA matrix), from --letter-bounds
+    pub letter_bounds: Option<LetterBounds>,
+    /// Column choice of the SoA dlx (the AoS backend always uses MRV)
+    pub dlx_heuristic: ColumnHeuristic,
+    /// Seed for the randomized parts of the solvers
+    pub seed: Option<u64>,
+    /// Only find this many random anagram-class combos, one word each (dlx with the SoA matrix
+    /// only)
+    pub sample: Option<usize>,
+    /// How --sample draws its solutions
+    pub sampler: Sampler,
+    /// Number of random probes for estimate
+    pub probes: Option<usize>,
+    /// Words every solution must contain (--include, repeatable)
+    pub include: Vec<String>,
+    /// Words no solution may contain (--exclude, repeatable)
+    pub exclude: Vec<String>,
+    /// Letters every solution must use, as a bitmask
+    pub require_letters: u32,
+    /// The letter solutions must leave out, as a bit index
+    pub unused_letter: Option<u32>,
+    /// File of `word frequency` lines for rank, on top of the word list's own column
+    pub freq_file: Option<PathBuf>,
+    /// Number of results rank, wordle and cover keep
+    pub top: Option<usize>,
+    /// Score rank orders solutions by
+    pub rank_by: RankBy,
+    /// Words need one of these tags, if any are given
+    pub allow_tags: Vec<String>,
+    /// Words with any of these tags are dropped
+    pub deny_tags: Vec<String>,
+    /// How solutions are printed
+    pub format: OutputFormat,
+    /// Checkpoint file to continue an interrupted search from, and to save progress to
+    pub resume: Option<PathBuf>,
+    /// Part of the top-level branches this process searches
+    pub shard: Option<Shard>,
+    /// Seconds serve gives a search before answering 504
+    pub timeout: Option<usize>,
+    /// Searches serve runs at once
+    pub max_concurrent: Option<usize>,
+    /// What wordle ranks openers by
+    pub wordle_score: WordleScore,
+    /// Print the sizes of the solvers' lookup tables to stderr (bench turns it on)
+    pub verbose: bool,
+}
+
+impl Options {
+    /// Splits the flags out of `args`, returning them with the remaining positional arguments.
+    pub fn parse(args: &[String]) -> (Options, Vec<String>) {
+        let mut options = Options::default();
+        let mut positional = Vec::new();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg.clone());
+                continue;
+            }
+            let value = match iter.next() {
+                Some(value) => value,
+                None => panic!("missing value for {}", arg),
+            };
+            match arg.as_str() {
+                "--mem-cap" => options.mem_cap = Some(parse_number(arg, value) * 1024 * 1024),
+                "--spill-dir" => options.spill_dir = Some(PathBuf::from(value)),
+                "--threads" => options.threads = Some(parse_number(arg, value)),
+                "--dlx-backend" => {
+                    options.dlx_backend = match DlxBackend::from_name(value) {
+                        Some(backend) => backend,
+                        None => panic!("unknown dlx backend {} (expected aos or soa)", value),
+                    }
+                }
+                "--dlx-heuristic" => {
+                    options.dlx_heuristic = match ColumnHeuristic::from_name(value) {
+                        Some(heuristic) => heuristic,
+                        None => panic!(
+                        "unknown dlx heuristic {} (expected mrv, first, static, random or buckets)",
+                        value
+                    ),
+                    }
+                }
+                "--seed" => options.seed = Some(parse_number(arg, value) as u64),
+                "--sample" => options.sample = Some(parse_number(arg, value)),
+                "--sampler" => {
+                    options.sampler = match Sampler::from_name(value) {
+                        Some(sampler) => sampler,
+                        None => panic!("unknown sampler {} (expected shuffle or uniform)", value),
+                    }
+                }
+                "--probes" => options.probes = Some(parse_positive(arg, value)),
+                "--include" => options.include.push(value.clone()),
+                "--exclude" => options.exclude.push(value.clone()),
+                "--require-letters" => match parse_letters(value) {
+                    Some(letters) => options.require_letters |= letters,
+                    None => panic!("invalid letters {} for {}", value, arg),
+                },
+                "--unused-letter" => match parse_letters(value) {
+                    Some(letter) if letter.count_ones() == 1 => {
+                        options.unused_letter = Some(letter.trailing_zeros())
+                    }
+                    _ => panic!("invalid letter {} for {}", value, arg),
+                },
+                "--freq-file" => options.freq_file = Some(PathBuf::from(value)),
+                "--top" => options.top = Some(parse_number(arg, value)),
+                "--rank-by" => {
+                    options.rank_by = match RankBy::from_name(value) {
+                        Some(rank_by) => rank_by,
+                        None => panic!("unknown ranking {} (expected logsum or min)", value),
+                    }
+                }
+                "--allow-tags" => options.allow_tags.extend(split_list(value)),
+                "--deny-tags" => options.deny_tags.extend(split_list(value)),
+                "--format" => {
+                    options.format = match OutputFormat::from_name(value) {
+                        Some(format) => format,
+                        None => panic!("unknown format {} (expected text or json)", value),
+                    }
+                }
+                "--resume" => options.resume = Some(PathBuf::from(value)),
+                "--shard" => match Shard::parse(value) {
+                    Some(shard) => options.shard = Some(shard),
+                    None => panic!("invalid shard {} (expected i/n with i < n)", value),
+                },
+                "--timeout" => options.timeout = Some(parse_number(arg, value)),
+                "--max-concurrent" => options.max_concurrent = Some(parse_number(arg, value)),
+                "--wordle-score" => {
+                    options.wordle_score = match WordleScore::from_name(value) {
+                        Some(score) => score,
+                        None => panic!(
+                            "unknown wordle score {} (expected expected or entropy)",
+                            value
+                        ),
+                    }
+                }
+                "--letter-bounds" => match parse_letter_bounds(value) {
+                    Some(bounds) => options.letter_bounds = Some(bounds),
+                    None => panic!("invalid letter bounds {} (expected e.g. aeiou=0..2)", value),
+                },
+                _ => panic!("unknown flag {}", arg),
+            }
+        }
+        (options, positional)
+    }
+}
+
+/// Comma-separated list, as in `--deny-tags slang,vulgar`
+fn split_list(value: &str) -> impl Iterator<Item = String> + '_ {
+    value
+        .split(',')
+        .filter(|item| !item.is_empty())
+        .map(|item| item.to_string())
+}
+
+fn parse_number(flag: &str, value: &str) -> usize {
+    match value.parse() {
+        Ok(n) => n,
+        Err(why) => panic!("invalid value {} for {}: {}", value, flag, why),
+    }
+}
+
+fn parse_positive(flag: &str, value: &str) -> usize {
+    match parse_number(flag, value) {
+        0 => panic!("{} must be at least 1", flag),
+        n => n,
+    }
+}
+
+#[test]
+fn test_parse_options() {
+    let args: Vec<String> = [
+        "words.txt",
+        "--mem-cap",
+        "64",
+        "smart_brute_bounded",
+        "--spill-dir",
+        "/tmp/x",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    let (options, positional) = Options::parse(&args);
+    assert_eq!(positional, vec!["words.txt", "smart_brute_bounded"]);
+    assert_eq!(options.mem_cap, Some(64 * 1024 * 1024));
+    assert_eq!(options.spill_dir, Some(PathBuf::from("/tmp/x")));
+}
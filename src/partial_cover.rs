@@ -0,0 +1,310 @@
+// cover: the best partial solutions, for word lists where no five words use 25 letters.
+//
+// Looks for sets of up to K words with pairwise disjoint letters and ranks them by how many
+// distinct letters they cover. Only sets that no other word could be added to are reported, so
+// the top --top aren't crowded out by subsets of each other. The search is a depth-first walk
+// over the anagram keys like brute's, carrying the keys still disjoint with the set so far
+// (filtered with `filter_disjoint`); a branch is cut once even the letters of all its remaining
+// candidates can't lift it above the worst set kept.
+//
+// --include seeds the sets with the included words, --unused-letter drops the words with that
+// letter and --require-letters only keeps sets that use all of them. A partial cover leaves out
+// several letters anyway, so unlike in the solvers --unused-letter doesn't require the others.
+
+use prettytable::{Cell, Row, Table};
+
+use crate::constraints::Constraints;
+use crate::lexicon::{OutputFormat, Tags};
+use crate::options::Options;
+use crate::ranking::TopK;
+use crate::simd_filter::filter_disjoint;
+use crate::word_reprs::build_word_representations;
+
+const DEFAULT_TOP: usize = 10;
+const DEFAULT_SET_SIZE: usize = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cover {
+    /// The keys of the set, followed by zeros when it has fewer than 5
+    pub combo: [u32; 5],
+    pub letters: u32,
+}
+
+impl Cover {
+    pub fn keys(&self) -> &[u32] {
+        let len = self.combo.iter().take_while(|key| **key != 0).count();
+        &self.combo[..len]
+    }
+}
+
+struct Search<'a> {
+    keys: &'a [u32],
+    size: usize,
+    /// Letters every set must use
+    letters: u32,
+}
+
+impl Search<'_> {
+    fn aux(
+        &self,
+        depth: usize,
+        combo: [u32; 5],
+        combo_repr: u32,
+        candidates: &[u32],
+        results: &mut TopK,
+    ) {
+        let letters = combo_repr.count_ones();
+        let reachable = candidates.iter().fold(0, |acc, key| acc | key);
+        if self.letters & !(combo_repr | reachable) != 0 {
+            return;
+        }
+        if depth == self.size || candidates.is_empty() {
+            if self.letters & !combo_repr != 0 {
+                return;
+            }
+            if results
+                .threshold()
+                .is_some_and(|threshold| letters as f64 <= threshold)
+            {
+                return;
+            }
+            // A key before the ones tried here may still fit, and then this set is part of a
+            // bigger one
+            if depth < self.size && self.keys.iter().any(|key| key & combo_repr == 0) {
+                return;
+            }
+            results.push(letters as f64, combo);
+            return;
+        }
+
+        if let Some(threshold) = results.threshold() {
+            let widest = candidates.iter().map(|key| key.count_ones()).max().unwrap();
+            let bound = letters
+                + reachable
+                    .count_ones()
+                    .min(widest * (self.size - depth) as u32);
+            if bound as f64 <= threshold {
+                return;
+            }
+        }
+
+        let mut new_combo = combo;
+        let mut next = Vec::with_capacity(candidates.len());
+        for (i, key) in candidates.iter().enumerate() {
+            new_combo[depth] = *key;
+            next.clear();
+            filter_disjoint(&candidates[i + 1..], *key, &mut next);
+            self.aux(depth + 1, new_combo, combo_repr | key, &next, results);
+        }
+    }
+}
+
+/// The `top` sets of at most `size` keys with disjoint letters that cover the most letters, best
+/// first. Every set contains the keys of `constraints.required`, the rest are its free keys, and
+/// it uses all of `constraints.letters`.
+pub fn best_covers(keys: &[u32], size: usize, top: usize, constraints: &Constraints) -> Vec<Cover> {
+    assert!((1..=5).contains(&size), "set size must be between 1 and 5");
+    let mut results = TopK::new(top);
+    if top == 0 || !constraints.feasible() || constraints.required.len() > size {
+        return Vec::new();
+    }
+    let keys = &constraints.free_keys(keys);
+    let (combo, depth, combo_repr) = constraints.seed();
+    if depth == 0 && keys.is_empty() {
+        return Vec::new();
+    }
+    let search = Search {
+        keys,
+        size,
+        letters: constraints.letters,
+    };
+    search.aux(depth, combo, combo_repr, keys, &mut results);
+    results
+        .into_sorted_vec()
+        .into_iter()
+        .map(|ranked| {
+            let mut combo = ranked.combo;
+            combo.sort_by(|a, b| b.cmp(a)); // zeros last
+            Cover {
+                combo,
+                letters: ranked.score as u32,
+            }
+        })
+        .collect()
+}
+
+fn letters_of(mask: u32) -> String {
+    (0..26)
+        .filter(|i| mask & (1 << i) != 0)
+        .map(|i| (b'a' + i as u8) as char)
+        .collect()
+}
+
+/// `cover [K]`: prints the best --top sets of up to K words (5 by default) with disjoint
+/// letters, by the number of letters they cover.
+pub fn cover(words: Vec<String>, tags: &Tags, args: &[String], options: &Options) {
+    let size = match args.first().map(|size| size.parse()) {
+        None => DEFAULT_SET_SIZE,
+        Some(Ok(size)) if (1..=5).contains(&size) => size,
+        Some(_) => panic!("invalid set size {} (expected 1 to 5)", args[0]),
+    };
+    let (reprs, keys) = build_word_representations(&words);
+    let top = options.top.unwrap_or(DEFAULT_TOP);
+    let constraints = Constraints {
+        letters: options.require_letters,
+        ..Constraints::new(options)
+    };
+    let covers = best_covers(&keys, size, top, &constraints);
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("rank"),
+        Cell::new("letters"),
+        Cell::new("missing"),
+        Cell::new("words"),
+    ]));
+    let all_letters = (1 << 26) - 1;
+    for (i, cover) in covers.iter().enumerate() {
+        let repr = cover.keys().iter().fold(0, |acc, key| acc | key);
+        let missing = letters_of(all_letters & !repr);
+        if options.format == OutputFormat::Json {
+            let words: Vec<&str> = cover.keys().iter().map(|key| reprs[key][0]).collect();
+            println!(
+                "{{\"rank\":{},\"letters\":{},\"missing\":\"{}\",\"words\":{}}}",
+                i + 1,
+                cover.letters,
+                missing,
+                tags.json_words(&words)
+            );
+            continue;
+        }
+        // Anagrams are interchangeable, so each class is printed as a whole
+        let classes: Vec<String> = cover
+            .keys()
+            .iter()
+            .map(|key| reprs[key].join("/"))
+            .collect();
+        table.add_row(Row::new(vec![
+            Cell::new(&(i + 1).to_string()),
+            Cell::new(&cover.letters.to_string()),
+            Cell::new(&missing),
+            Cell::new(&classes.join(" ")),
+        ]));
+    }
+    if options.format == OutputFormat::Text {
+        table.printstd();
+    }
+}
+
+// Letters of every set of disjoint keys that extends `set` and can't be extended itself
+#[cfg(test)]
+fn maximal(keys: &[u32], size: usize, set: &mut Vec<u32>, pos: usize, out: &mut Vec<u32>) {
+    let repr = set.iter().fold(0, |acc, key| acc | key);
+    let fits = |key: &u32| key & repr == 0;
+    if set.len() == size || !keys.iter().any(fits) {
+        out.push(repr);
+        return;
+    }
+    for i in pos..keys.len() {
+        if fits(&keys[i]) {
+            set.push(keys[i]);
+            maximal(keys, size, set, i + 1, out);
+            set.pop();
+        }
+    }
+}
+
+#[test]
+fn test_best_covers_match_exhaustive() {
+    use crate::word_reprs::*;
+
+    // Without the v and x words no five of these are disjoint
+    let words: Vec<String> = test_words()
+        .into_iter()
+        .filter(|word| !word.contains('v') && !word.contains('x'))
+        .collect();
+    let (_reprs, keys) = build_word_representations(&words);
+    assert!(collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys).is_empty());
+
+    for size in 1..=5 {
+        let mut expected = Vec::new();
+        maximal(&keys, size, &mut Vec::new(), 0, &mut expected);
+        let mut expected: Vec<u32> = expected.iter().map(|repr| repr.count_ones()).collect();
+        expected.sort_by(|a, b| b.cmp(a));
+        for top in [1, 4, 100] {
+            let covers = best_covers(&keys, size, top, &Constraints::default());
+            let letters: Vec<u32> = covers.iter().map(|cover| cover.letters).collect();
+            assert_eq!(
+                letters,
+                expected[..top.min(expected.len())],
+                "size {}",
+                size
+            );
+            for cover in covers.iter() {
+                let repr = cover.keys().iter().fold(0, |acc, key| acc | key);
+                assert_eq!(repr.count_ones(), cover.letters);
+                assert!(cover.keys().len() <= size);
+            }
+        }
+    }
+
+    // With perfect solutions around, those come first
+    let (_reprs, keys) = build_word_representations(&test_words());
+    let perfect = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    let covers = best_covers(&keys, 5, perfect.len(), &Constraints::default());
+    let mut found: Vec<[u32; 5]> = covers
+        .iter()
+        .map(|cover| {
+            let mut combo = cover.combo;
+            combo.sort();
+            combo
+        })
+        .collect();
+    found.sort();
+    assert_eq!(found, perfect);
+    assert!(covers.iter().all(|cover| cover.letters == 25));
+}
+
+#[test]
+fn test_best_covers_constrained() {
+    use crate::word_reprs::*;
+
+    let words: Vec<String> = test_words()
+        .into_iter()
+        .filter(|word| !word.contains('v') && !word.contains('x'))
+        .collect();
+    let (_reprs, keys) = build_word_representations(&words);
+    let options = Options {
+        include: vec![String::from("fjord")],
+        require_letters: 1 << (b'q' - b'a'),
+        unused_letter: Some((b'z' - b'a') as u32),
+        ..Options::default()
+    };
+    let constraints = Constraints {
+        letters: options.require_letters,
+        ..Constraints::new(&options)
+    };
+    let fjord = get_repr("fjord");
+    let banned = 1 << (b'z' - b'a');
+
+    for size in 1..=5 {
+        let mut expected = Vec::new();
+        let free = constraints.free_keys(&keys);
+        maximal(&free, size, &mut vec![fjord], 0, &mut expected);
+        let mut expected: Vec<u32> = expected
+            .iter()
+            .filter(|repr| *repr & constraints.letters == constraints.letters)
+            .map(|repr| repr.count_ones())
+            .collect();
+        expected.sort_by(|a, b| b.cmp(a));
+        let covers = best_covers(&keys, size, 100, &constraints);
+        let letters: Vec<u32> = covers.iter().map(|cover| cover.letters).collect();
+        assert_eq!(letters, expected, "size {}", size);
+        assert_eq!(covers.is_empty(), size == 1);
+        for cover in covers.iter() {
+            let repr = cover.keys().iter().fold(0, |acc, key| acc | key);
+            assert!(cover.keys().contains(&fjord));
+            assert_eq!(repr & (banned | constraints.letters), constraints.letters);
+        }
+    }
+}
@@ -0,0 +1,367 @@
+// Ranks solutions by how common their words are and keeps the best --top of them.
+//
+// Frequencies come from an optional second column of the word list (`word frequency`) or from
+// --freq-file in the same format; a word without one counts as frequency 0. An anagram class
+// scores as its most frequent word, which is also the word printed for it.
+//
+// The search is `aux` from backtracking_brute with branch-and-bound: keys are tried from the
+// highest weight down, so the best score any extension of a partial combo can still reach is
+// bounded by the weight of the next candidate, and once that bound can't beat the current
+// top-K the rest of the level is cut off.
+//
+// It is a search of its own rather than a bound threaded through the `SOLVERS`: those hand
+// combos to a `ComboSink` that can't report a threshold back, and they depend on their own key
+// order (ascending keys for the pair tables and the compat index, column choice in DLX), while
+// the bound needs the keys by descending weight.
+
+use prettytable::{Cell, Row, Table};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufReader;
+
+use crate::constraints::{Constraints, Reach};
+use crate::lexicon::{parse_line, Entry, OutputFormat, Tags};
+use crate::options::Options;
+use crate::word_reprs::build_word_representations;
+
+const DEFAULT_TOP: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RankBy {
+    /// Sum of ln(1 + frequency) over the five words
+    #[default]
+    LogSum,
+    /// Frequency of the rarest word
+    Min,
+}
+
+impl RankBy {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "logsum" => Some(RankBy::LogSum),
+            "min" => Some(RankBy::Min),
+            _ => None,
+        }
+    }
+
+    fn weight(self, frequency: f64) -> f64 {
+        match self {
+            RankBy::LogSum => frequency.ln_1p(),
+            RankBy::Min => frequency,
+        }
+    }
+
+    fn empty_score(self) -> f64 {
+        match self {
+            RankBy::LogSum => 0.0,
+            RankBy::Min => f64::INFINITY,
+        }
+    }
+
+    fn combine(self, score: f64, weight: f64) -> f64 {
+        match self {
+            RankBy::LogSum => score + weight,
+            RankBy::Min => score.min(weight),
+        }
+    }
+
+    // Best final score when `remaining` more words of weight at most `weight` are added
+    fn bound(self, score: f64, weight: f64, remaining: usize) -> f64 {
+        match self {
+            RankBy::LogSum => score + weight * remaining as f64,
+            RankBy::Min => score.min(weight),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Frequencies(HashMap<String, f64>);
+
+impl Frequencies {
+    /// Frequencies from the word list's entries, overridden by the ones in --freq-file.
+    pub fn load(entries: &[Entry], options: &Options) -> Self {
+        let mut frequencies = Frequencies::default();
+        frequencies.add_entries(entries.iter().cloned());
+        if let Some(path) = &options.freq_file {
+            let file = match File::open(path) {
+                Err(why) => panic!("couldn't open {}: {}", path.display(), why),
+                Ok(file) => file,
+            };
+            let lines: Vec<String> = BufReader::new(file)
+                .lines()
+                .map(|line| line.unwrap_or_default())
+                .collect();
+            frequencies.add_entries(lines.iter().map(|line| parse_line(line)));
+        }
+        frequencies
+    }
+
+    fn add_entries(&mut self, entries: impl Iterator<Item = Entry>) {
+        for entry in entries {
+            if let Some(frequency) = entry.frequency {
+                self.0.insert(entry.word, frequency);
+            }
+        }
+    }
+
+    pub fn get(&self, word: &str) -> f64 {
+        self.0.get(word).copied().unwrap_or(0.0)
+    }
+}
+
+/// A scored combo, with whatever else the ranking wants to keep about it in `extra`.
+#[derive(Debug, Clone, Copy)]
+pub struct Ranked<T = ()> {
+    pub score: f64,
+    pub combo: [u32; 5],
+    pub extra: T,
+}
+
+impl<T> PartialEq for Ranked<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T> Eq for Ranked<T> {}
+
+impl<T> PartialOrd for Ranked<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Ranked<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| self.combo.cmp(&other.combo))
+    }
+}
+
+/// The k best combos seen so far, in a min-heap so the worst of them is dropped first.
+pub struct TopK<T = ()> {
+    k: usize,
+    heap: BinaryHeap<Reverse<Ranked<T>>>,
+}
+
+impl<T> TopK<T> {
+    pub fn new(k: usize) -> Self {
+        TopK {
+            k,
+            heap: BinaryHeap::with_capacity(k + 1),
+        }
+    }
+
+    /// Score a combo has to beat to get in, once k combos are kept
+    pub fn threshold(&self) -> Option<f64> {
+        if self.heap.len() < self.k {
+            None
+        } else {
+            self.heap.peek().map(|Reverse(worst)| worst.score)
+        }
+    }
+
+    pub fn push_with(&mut self, score: f64, combo: [u32; 5], extra: T) {
+        self.heap.push(Reverse(Ranked {
+            score,
+            combo,
+            extra,
+        }));
+        if self.heap.len() > self.k {
+            self.heap.pop();
+        }
+    }
+
+    /// Keeps the k best of both, e.g. to combine the results of several threads
+    pub fn merge(mut self, other: Self) -> Self {
+        for Reverse(ranked) in other.heap {
+            self.push_with(ranked.score, ranked.combo, ranked.extra);
+        }
+        self
+    }
+
+    /// Best first
+    pub fn into_sorted_vec(self) -> Vec<Ranked<T>> {
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(ranked)| ranked)
+            .collect()
+    }
+}
+
+impl TopK {
+    pub fn push(&mut self, score: f64, combo: [u32; 5]) {
+        self.push_with(score, combo, ());
+    }
+}
+
+/// The `top` best combos of `keys`, with `weights` giving each key's weight.
+pub fn top_combos(
+    keys: &[u32],
+    weights: &HashMap<u32, f64>,
+    rank_by: RankBy,
+    top: usize,
+    options: &Options,
+) -> Vec<Ranked> {
+    let mut results = TopK::new(top);
+    let constraints = Constraints::new(options);
+    if top == 0 || !constraints.feasible() {
+        return Vec::new();
+    }
+    let mut keys = constraints.free_keys(keys);
+    keys.sort_by(|a, b| weights[b].total_cmp(&weights[a]).then(a.cmp(b)));
+    let key_weights: Vec<f64> = keys.iter().map(|key| weights[key]).collect();
+
+    let (combo, depth, combo_repr) = constraints.seed();
+    let score = combo[..depth]
+        .iter()
+        .fold(rank_by.empty_score(), |score, key| {
+            rank_by.combine(score, weights[key])
+        });
+    let search = Search {
+        keys: &keys,
+        weights: &key_weights,
+        rank_by,
+        reach: constraints.reach(&keys),
+    };
+    search.aux(depth, combo, combo_repr, score, 0, &mut results);
+    results.into_sorted_vec()
+}
+
+struct Search<'a> {
+    keys: &'a [u32],
+    weights: &'a [f64],
+    rank_by: RankBy,
+    reach: Reach,
+}
+
+impl Search<'_> {
+    fn aux(
+        &self,
+        depth: usize,
+        combo: [u32; 5],
+        combo_repr: u32,
+        score: f64,
+        pos: usize,
+        results: &mut TopK,
+    ) {
+        if !self.reach.allows(combo_repr, depth, pos) {
+            return;
+        }
+        if depth == 5 {
+            results.push(score, combo);
+            return;
+        }
+
+        let mut new_combo = combo;
+        for pos2 in pos..self.keys.len() {
+            let weight = self.weights[pos2];
+            if let Some(threshold) = results.threshold() {
+                // Later keys weigh no more, so they can't do better either
+                if self.rank_by.bound(score, weight, 5 - depth) <= threshold {
+                    break;
+                }
+            }
+            let key = self.keys[pos2];
+            if key & combo_repr != 0 {
+                continue;
+            }
+            new_combo[depth] = key;
+            let new_score = self.rank_by.combine(score, weight);
+            self.aux(
+                depth + 1,
+                new_combo,
+                combo_repr | key,
+                new_score,
+                pos2 + 1,
+                results,
+            );
+        }
+    }
+}
+
+pub fn rank(words: Vec<String>, frequencies: &Frequencies, tags: &Tags, options: &Options) {
+    let (reprs, keys) = build_word_representations(&words);
+    let rank_by = options.rank_by;
+
+    // Each anagram class is represented by its most frequent word
+    let mut best_words: HashMap<u32, &str> = HashMap::new();
+    let mut weights: HashMap<u32, f64> = HashMap::new();
+    for (key, class_words) in reprs.iter() {
+        let best = class_words
+            .iter()
+            .max_by(|a, b| frequencies.get(a).total_cmp(&frequencies.get(b)))
+            .unwrap();
+        best_words.insert(*key, best);
+        weights.insert(*key, rank_by.weight(frequencies.get(best)));
+    }
+
+    let top = options.top.unwrap_or(DEFAULT_TOP);
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("rank"),
+        Cell::new("score"),
+        Cell::new("words"),
+    ]));
+    for (i, ranked) in top_combos(&keys, &weights, rank_by, top, options)
+        .iter()
+        .enumerate()
+    {
+        let mut combo = ranked.combo;
+        combo.sort_by(|a, b| weights[b].total_cmp(&weights[a]));
+        let words: Vec<&str> = combo.iter().map(|key| best_words[key]).collect();
+        if options.format == OutputFormat::Json {
+            println!(
+                "{{\"rank\":{},\"score\":{},\"words\":{}}}",
+                i + 1,
+                ranked.score,
+                tags.json_words(&words)
+            );
+            continue;
+        }
+        table.add_row(Row::new(vec![
+            Cell::new(&(i + 1).to_string()),
+            Cell::new(&format!("{:.3}", ranked.score)),
+            Cell::new(&words.join(" ")),
+        ]));
+    }
+    if options.format == OutputFormat::Text {
+        table.printstd();
+    }
+}
+
+#[test]
+fn test_top_combos_match_exhaustive() {
+    use crate::word_reprs::*;
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let all = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    assert!(all.len() > 3);
+
+    for rank_by in [RankBy::LogSum, RankBy::Min] {
+        // Any spread of frequencies will do
+        let weights: HashMap<u32, f64> = keys
+            .iter()
+            .map(|key| (*key, rank_by.weight((key % 997) as f64)))
+            .collect();
+        let score = |combo: &[u32; 5]| {
+            combo.iter().fold(rank_by.empty_score(), |score, key| {
+                rank_by.combine(score, weights[key])
+            })
+        };
+        let mut expected: Vec<f64> = all.iter().map(score).collect();
+        expected.sort_by(|a, b| b.total_cmp(a));
+        expected.truncate(3);
+
+        let found = top_combos(&keys, &weights, rank_by, 3, &Options::default());
+        assert!(found
+            .iter()
+            .all(|ranked| ranked.score == score(&ranked.combo)));
+        let scores: Vec<f64> = found.iter().map(|ranked| ranked.score).collect();
+        assert_eq!(scores, expected, "{:?}", rank_by);
+    }
+}
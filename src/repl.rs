@@ -0,0 +1,399 @@
+// repl: an interactive session for narrowing down solutions one constraint at a time.
+//
+// The word list's anagram classes stay loaded, and the solver runs once per `load`. `include`
+// and `exclude` can only take solutions away, so instead of searching again they filter the
+// combos found so far; every state is kept, which makes `undo` free as well.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::prelude::*;
+use std::path::Path;
+use std::time::Instant;
+
+use itertools::Itertools;
+
+use crate::constraints::try_filter_words;
+use crate::index::{is_index, Index};
+use crate::options::Options;
+use crate::word_reprs::*;
+use crate::{find_solver, read_entries, SolverFn};
+
+const HELP: &str = "commands:
+  load FILE       read a new word list and solve it
+  include WORD    keep the solutions with WORD
+  exclude WORD    drop WORD from the solutions
+  count           number of solutions
+  show [N]        print the first N solutions (10)
+  stats           word list, constraints and solution counts
+  undo            take back the last include or exclude
+  quit";
+
+const DEFAULT_SHOW: usize = 10;
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    include: Vec<String>,
+    exclude: Vec<String>,
+    /// Key combos, each sorted, of the solutions left
+    combos: Vec<[u32; 5]>,
+}
+
+pub struct Repl {
+    solver: SolverFn,
+    options: Options,
+    /// Words of each anagram class of the loaded list
+    classes: HashMap<u32, Vec<String>>,
+    word_count: usize,
+    solve_ms: f64,
+    history: Vec<State>,
+}
+
+impl Repl {
+    pub fn new(words: &[String], solver: SolverFn, options: &Options) -> Self {
+        let mut repl = Repl {
+            solver,
+            options: options.clone(),
+            classes: HashMap::new(),
+            word_count: 0,
+            solve_ms: 0.0,
+            history: Vec::new(),
+        };
+        repl.solve(words);
+        repl
+    }
+
+    fn solve(&mut self, words: &[String]) {
+        let (reprs, keys) = build_word_representations(words);
+        let start = Instant::now();
+        let found = std::sync::Mutex::new(Vec::new());
+        (self.solver)(&keys, &self.options, &|mut combo| {
+            combo.sort();
+            found.lock().unwrap().push(combo);
+        });
+        let mut combos = found.into_inner().unwrap();
+        combos.sort();
+        self.solve_ms = start.elapsed().as_secs_f64() * 1000.0;
+        self.classes = reprs
+            .into_iter()
+            .map(|(key, words)| (key, words.iter().map(|w| w.to_string()).collect()))
+            .collect();
+        self.word_count = words.len();
+        self.history = vec![State {
+            combos,
+            ..State::default()
+        }];
+    }
+
+    fn state(&self) -> &State {
+        self.history.last().unwrap()
+    }
+
+    /// Words of `key` still allowed by the current constraints
+    fn words_of(&self, key: u32) -> Vec<&str> {
+        let state = self.state();
+        let words = self.classes[&key].iter().map(|w| w.as_str());
+        let included: Vec<&str> = words
+            .clone()
+            .filter(|word| state.include.iter().any(|w| w == word))
+            .collect();
+        if !included.is_empty() {
+            return included;
+        }
+        words
+            .filter(|word| !state.exclude.iter().any(|w| w == word))
+            .collect()
+    }
+
+    fn solution_count(&self, combo: &[u32; 5]) -> usize {
+        combo.iter().map(|key| self.words_of(*key).len()).product()
+    }
+
+    fn find_key(&self, word: &str) -> Result<u32, String> {
+        // get_repr only takes lowercase letters
+        if !word.bytes().all(|byte| byte.is_ascii_lowercase()) {
+            return Err(format!("{} is not in the word list", word));
+        }
+        let key = get_repr(word);
+        match self.classes.get(&key) {
+            Some(words) if words.iter().any(|w| w == word) => Ok(key),
+            _ => Err(format!("{} is not in the word list", word)),
+        }
+    }
+
+    fn push(&mut self, state: State) -> String {
+        self.history.push(state);
+        self.count()
+    }
+
+    fn include(&mut self, word: &str) -> Result<String, String> {
+        let key = self.find_key(word)?;
+        let mut state = self.state().clone();
+        if state.include.iter().any(|w| w == word) {
+            return Err(format!("{} is already included", word));
+        }
+        if let Some(anagram) = state.include.iter().find(|w| get_repr(w) == key) {
+            return Err(format!(
+                "{} is an anagram of the included {}, so no solution has both",
+                word, anagram
+            ));
+        }
+        state.include.push(word.to_string());
+        state.exclude.retain(|w| w != word);
+        state.combos.retain(|combo| combo.contains(&key));
+        Ok(self.push(state))
+    }
+
+    fn exclude(&mut self, word: &str) -> Result<String, String> {
+        self.find_key(word)?;
+        let mut state = self.state().clone();
+        if state.include.iter().any(|w| w == word) {
+            return Err(format!("{} is included, undo that first", word));
+        }
+        state.exclude.push(word.to_string());
+        self.history.push(state);
+        // Combos whose class has no word left are gone
+        let combos: Vec<[u32; 5]> = self
+            .state()
+            .combos
+            .iter()
+            .filter(|combo| self.solution_count(combo) > 0)
+            .copied()
+            .collect();
+        self.history.last_mut().unwrap().combos = combos;
+        Ok(self.count())
+    }
+
+    fn count(&self) -> String {
+        let combos = &self.state().combos;
+        let solutions: usize = combos.iter().map(|c| self.solution_count(c)).sum();
+        format!("{} solutions ({} anagram combos)", solutions, combos.len())
+    }
+
+    fn show(&self, n: usize) -> String {
+        self.state()
+            .combos
+            .iter()
+            .flat_map(|combo| {
+                combo
+                    .iter()
+                    .map(|key| self.words_of(*key))
+                    .multi_cartesian_product()
+            })
+            .take(n)
+            .map(|words| words.join(" "))
+            .join("\n")
+    }
+
+    fn stats(&self) -> String {
+        let state = self.state();
+        let mut unused = [0usize; 26];
+        for combo in state.combos.iter() {
+            let letters = combo.iter().fold(0, |acc, key| acc | key);
+            let letter = (!letters & ((1 << 26) - 1)).trailing_zeros() as usize;
+            if letter < 26 {
+                unused[letter] += self.solution_count(combo);
+            }
+        }
+        let unused = (0..26)
+            .filter(|i| unused[*i] > 0)
+            .sorted_by_key(|i| std::cmp::Reverse(unused[*i]))
+            .map(|i| format!("{} {}", (b'a' + i as u8) as char, unused[i]))
+            .join(", ");
+        format!(
+            "{} words in {} anagram classes, solved in {:.1} ms\n\
+             include: {}\nexclude: {}\n{}\nunused letters: {}",
+            self.word_count,
+            self.classes.len(),
+            self.solve_ms,
+            state.include.join(" "),
+            state.exclude.join(" "),
+            self.count(),
+            unused
+        )
+    }
+
+    fn load(&mut self, path: &str) -> Result<String, String> {
+        let bytes = fs::read(path).map_err(|why| format!("couldn't read {}: {}", path, why))?;
+        let words: Vec<String> = if is_index(&bytes) {
+            Index::try_load(Path::new(path), &bytes)?
+                .words()
+                .map(String::from)
+                .collect()
+        } else {
+            read_entries(&bytes, &self.options)
+                .into_iter()
+                .map(|entry| entry.word)
+                .collect()
+        };
+        let words = try_filter_words(words, &self.options)?;
+        self.solve(&words);
+        Ok(self.count())
+    }
+
+    /// Runs one command line and returns what it prints.
+    pub fn execute(&mut self, line: &str) -> String {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or("");
+        let arg = parts.next();
+        let result = match (command, arg) {
+            ("", _) => Ok(String::new()),
+            ("load", Some(path)) => self.load(path),
+            ("include", Some(word)) => self.include(word),
+            ("exclude", Some(word)) => self.exclude(word),
+            ("count", None) => Ok(self.count()),
+            ("show", None) => Ok(self.show(DEFAULT_SHOW)),
+            ("show", Some(n)) => match n.parse() {
+                Ok(n) => Ok(self.show(n)),
+                Err(_) => Err(format!("invalid number {}", n)),
+            },
+            ("stats", None) => Ok(self.stats()),
+            ("undo", None) if self.history.len() > 1 => {
+                self.history.pop();
+                Ok(self.count())
+            }
+            ("undo", None) => Err("nothing to undo".to_string()),
+            ("help", _) => Ok(HELP.to_string()),
+            _ => Err(format!("unknown command {:?}, try help", line.trim())),
+        };
+        result.unwrap_or_else(|message| format!("error: {}", message))
+    }
+}
+
+/// `repl [solver]`: reads commands from stdin until `quit` or end of input.
+pub fn repl(words: Vec<String>, args: &[String], options: &Options) {
+    let name = args.first().map_or("dlx", |name| name.as_str());
+    let solver = match find_solver(name) {
+        Some(solver) => solver,
+        None => panic!("unknown solver {}", name),
+    };
+    let mut repl = Repl::new(&words, solver, options);
+    println!("{}", repl.count());
+    let stdin = std::io::stdin();
+    loop {
+        print!("> ");
+        let _ = std::io::stdout().flush();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        if matches!(line.trim(), "quit" | "exit") {
+            break;
+        }
+        let output = repl.execute(&line);
+        if !output.is_empty() {
+            println!("{}", output);
+        }
+    }
+}
+
+#[test]
+fn test_repl_session() {
+    use crate::backtracking_brute::backtracking_brute_keys;
+
+    let words = test_words();
+    let (reprs, keys) = build_word_representations(&words);
+    let count_with = |options: &Options| -> usize {
+        let words = crate::constraints::filter_words(words.clone(), options);
+        let (reprs, keys) = build_word_representations(&words);
+        collect_sorted_with(backtracking_brute_keys, &keys, options)
+            .iter()
+            .map(|combo| combo_word_count(combo, &reprs))
+            .sum()
+    };
+    let all = collect_sorted(backtracking_brute_keys, &keys);
+    let total: usize = all
+        .iter()
+        .map(|combo| combo_word_count(combo, &reprs))
+        .sum();
+
+    let mut repl = Repl::new(&words, backtracking_brute_keys, &Options::default());
+    let count = |n: usize| format!("{} solutions", n);
+    assert!(repl.execute("count").starts_with(&count(total)));
+    assert_eq!(repl.execute("show 3").lines().count(), 3);
+
+    let options = Options {
+        include: vec!["waltz".to_string()],
+        exclude: vec!["gucks".to_string()],
+        ..Options::default()
+    };
+    let included = Options {
+        exclude: Vec::new(),
+        ..options.clone()
+    };
+    assert!(repl
+        .execute("include waltz")
+        .starts_with(&count(count_with(&included))));
+    assert!(repl
+        .execute("exclude gucks")
+        .starts_with(&count(count_with(&options))));
+    assert!(repl
+        .execute("show 100")
+        .lines()
+        .all(|line| line.contains("waltz") && !line.contains("gucks")));
+    assert!(repl
+        .execute("stats")
+        .contains("include: waltz\nexclude: gucks"));
+
+    assert!(repl
+        .execute("undo")
+        .starts_with(&count(count_with(&included))));
+    assert!(repl.execute("undo").starts_with(&count(total)));
+    assert_eq!(repl.execute("undo"), "error: nothing to undo");
+    assert!(repl.execute("include xylyl").starts_with("error:"));
+    assert!(repl.execute("include Waltz").starts_with("error:"));
+    assert!(repl
+        .execute("frobnicate")
+        .starts_with("error: unknown command"));
+}
+
+#[test]
+fn test_repl_include_anagrams() {
+    use crate::backtracking_brute::backtracking_brute_keys;
+
+    let mut words = test_words();
+    words.extend(["angle", "glean"].map(String::from));
+    let mut repl = Repl::new(&words, backtracking_brute_keys, &Options::default());
+    assert!(!repl.execute("include angle").starts_with("error:"));
+    assert_eq!(
+        repl.execute("include glean"),
+        "error: glean is an anagram of the included angle, so no solution has both"
+    );
+    assert!(repl.execute("stats").contains("include: angle\n"));
+}
+
+#[test]
+fn test_repl_load_errors() {
+    use crate::backtracking_brute::backtracking_brute_keys;
+
+    let words = test_words();
+    let mut repl = Repl::new(&words, backtracking_brute_keys, &Options::default());
+    let before = repl.execute("count");
+
+    let dir = std::env::temp_dir().join(format!("flw-repl-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let index = dir.join("words.idx");
+    let mut bytes = Index::build(&words, &dir.join("words.txt"), false).to_bytes(0);
+    *bytes.last_mut().unwrap() ^= 1;
+    fs::write(&index, bytes).unwrap();
+    let list = dir.join("words.txt");
+    fs::write(&list, "fjord\nwaltz\n").unwrap();
+
+    let load = |path: &Path| format!("load {}", path.display());
+    assert!(repl
+        .execute(&load(&index))
+        .starts_with("error: couldn't load index"));
+    assert_eq!(repl.execute("count"), before);
+
+    let mut including = Repl::new(
+        &words,
+        backtracking_brute_keys,
+        &Options {
+            include: vec!["gucks".to_string()],
+            ..Options::default()
+        },
+    );
+    assert!(including
+        .execute(&load(&list))
+        .contains("gucks is not in the word list"));
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,530 @@
+// serve: answers solution queries over HTTP, so other tools don't have to run the binary.
+//
+//   GET /health                     {"words": ..., "keys": ...}
+//   GET /solutions?include=fjord&exclude=waltz&require=ae&unused=q&solver=dlx&limit=10&format=json
+//
+// include and exclude may be repeated and work like --include / --exclude, require and unused
+// like --require-letters / --unused-letter. Of the server's own flags, only the ones that tune
+// the solvers (see `query_options`) carry over to queries. The word list is loaded once; each
+// query runs one of the SOLVERS (dlx by default) in a thread of its own, and is answered with 504
+// if it takes longer than --timeout. The search stops as soon as it has found `limit` solutions
+// or timed out: its sink unwinds out of the solver with `Stop` (dlx only streams its solutions
+// with the default --dlx-backend soa, aos finishes the search first). The JSON answer counts the
+// solutions found and tells whether the search ran to the end (`complete`). At most
+// --max-concurrent searches run at a time, counting ones that timed out but haven't stopped yet;
+// queries beyond that get 503.
+
+use itertools::Itertools;
+use std::collections::HashMap;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::constraints::{filter_words, parse_letters};
+use crate::find_solver;
+use crate::lexicon::{json_string, Tags};
+use crate::options::Options;
+use crate::word_reprs::*;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+const DEFAULT_TIMEOUT_SECS: usize = 30;
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+const DEFAULT_LIMIT: usize = 100;
+
+// Words of each key and the sorted keys, like `build_word_representations` but owning the words
+type Reprs = (HashMap<u32, Vec<String>>, Vec<u32>);
+
+pub struct Service {
+    words: Vec<String>,
+    reprs: Reprs,
+    tags: Tags,
+    options: Options,
+    timeout: Duration,
+    max_concurrent: usize,
+    running: Arc<AtomicUsize>,
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: String,
+}
+
+impl Response {
+    fn json(status: u16, body: String) -> Self {
+        Response {
+            status,
+            content_type: "application/json",
+            body,
+        }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Response::json(status, format!("{{\"error\":{}}}", json_string(message)))
+    }
+}
+
+// Unwinding payload that stops a search from its sink
+struct Stop;
+
+// Held while a search runs; gives its slot back when dropped
+struct Permit(Arc<AtomicUsize>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Service {
+    pub fn new(words: Vec<String>, tags: Tags, options: &Options) -> Self {
+        Service {
+            reprs: owned_reprs(&words),
+            words,
+            tags,
+            options: options.clone(),
+            timeout: Duration::from_secs(options.timeout.unwrap_or(DEFAULT_TIMEOUT_SECS) as u64),
+            max_concurrent: options.max_concurrent.unwrap_or(DEFAULT_MAX_CONCURRENT),
+            running: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Answers connections on `listener` forever, each in a thread of its own.
+    pub fn run(self: Arc<Self>, listener: TcpListener) {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let service = Arc::clone(&self);
+            thread::spawn(move || service.handle(stream));
+        }
+    }
+
+    fn handle(self: Arc<Self>, mut stream: TcpStream) {
+        let _ = stream.set_read_timeout(Some(self.timeout));
+        let _ = stream.set_write_timeout(Some(self.timeout));
+        let response = match read_request_target(&stream) {
+            Some((method, target)) => self.route(&method, &target),
+            None => Response::error(400, "malformed request"),
+        };
+        let _ = write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            response.status,
+            reason(response.status),
+            response.content_type,
+            response.body.len(),
+            response.body
+        );
+    }
+
+    fn route(self: &Arc<Self>, method: &str, target: &str) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        match (method, path) {
+            ("GET", "/health") => Response::json(
+                200,
+                format!(
+                    "{{\"words\":{},\"keys\":{}}}",
+                    self.words.len(),
+                    self.reprs.1.len()
+                ),
+            ),
+            ("GET", "/solutions") => match self.parse_query(query) {
+                Ok(query) => self.solutions(query),
+                Err(message) => Response::error(400, &message),
+            },
+            (_, "/health" | "/solutions") => Response::error(405, "only GET is supported"),
+            _ => Response::error(404, "no such endpoint"),
+        }
+    }
+
+    /// Options a query starts from: the defaults, with the server's solver tuning flags
+    fn query_options(&self) -> Options {
+        Options {
+            mem_cap: self.options.mem_cap,
+            dlx_backend: self.options.dlx_backend,
+            dlx_heuristic: self.options.dlx_heuristic,
+            seed: self.options.seed,
+            ..Options::default()
+        }
+    }
+
+    fn parse_query(&self, query: &str) -> Result<Query, String> {
+        let mut options = self.query_options();
+        let mut parsed = Query {
+            solver: "dlx".to_string(),
+            limit: DEFAULT_LIMIT,
+            json: true,
+            options: Options::default(),
+        };
+        for (name, value) in query
+            .split('&')
+            .filter(|pair| !pair.is_empty())
+            .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        {
+            let value = percent_decode(value);
+            match name {
+                "include" | "exclude" => {
+                    if !self.words.contains(&value) {
+                        return Err(format!("{} is not in the word list", value));
+                    }
+                    match name {
+                        "include" => options.include.push(value),
+                        _ => options.exclude.push(value),
+                    }
+                }
+                "require" => match parse_letters(&value) {
+                    Some(letters) => options.require_letters |= letters,
+                    None => return Err(format!("invalid letters {}", value)),
+                },
+                "unused" => match parse_letters(&value) {
+                    Some(letter) if letter.count_ones() == 1 => {
+                        options.unused_letter = Some(letter.trailing_zeros())
+                    }
+                    _ => return Err(format!("invalid letter {}", value)),
+                },
+                "solver" if find_solver(&value).is_some() => parsed.solver = value,
+                "solver" => return Err(format!("unknown solver {}", value)),
+                "limit" => match value.parse() {
+                    Ok(limit) => parsed.limit = limit,
+                    Err(_) => return Err(format!("invalid limit {}", value)),
+                },
+                "format" => match value.as_str() {
+                    "json" => parsed.json = true,
+                    "text" => parsed.json = false,
+                    _ => return Err(format!("unknown format {} (expected json or text)", value)),
+                },
+                _ => return Err(format!("unknown parameter {}", name)),
+            }
+        }
+        if options
+            .include
+            .iter()
+            .any(|word| options.exclude.contains(word))
+        {
+            return Err("a word can't be both included and excluded".to_string());
+        }
+        parsed.options = options;
+        Ok(parsed)
+    }
+
+    fn solutions(self: &Arc<Self>, query: Query) -> Response {
+        let permit = match self.acquire() {
+            Some(permit) => permit,
+            None => return Response::error(503, "too many searches running"),
+        };
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let service = Arc::clone(self);
+        let search_cancelled = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let response = service.search(&query, &search_cancelled);
+            // Free the slot before answering, so that the next query can't find it still taken
+            drop(permit);
+            let _ = sender.send(response);
+        });
+        match receiver.recv_timeout(self.timeout) {
+            Ok(response) => response,
+            Err(RecvTimeoutError::Timeout) => {
+                cancelled.store(true, Ordering::Relaxed);
+                Response::error(504, "the search timed out")
+            }
+            Err(RecvTimeoutError::Disconnected) => Response::error(500, "the search failed"),
+        }
+    }
+
+    fn acquire(&self) -> Option<Permit> {
+        let mut running = self.running.load(Ordering::SeqCst);
+        loop {
+            if running >= self.max_concurrent {
+                return None;
+            }
+            match self.running.compare_exchange(
+                running,
+                running + 1,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return Some(Permit(Arc::clone(&self.running))),
+                Err(current) => running = current,
+            }
+        }
+    }
+
+    fn search(&self, query: &Query, cancelled: &AtomicBool) -> Response {
+        // The word filters change the anagram classes, anything else works on the loaded ones
+        let filtered;
+        let (reprs, keys) = if query.options.include.is_empty() && query.options.exclude.is_empty()
+        {
+            &self.reprs
+        } else {
+            filtered = owned_reprs(&filter_words(self.words.clone(), &query.options));
+            &filtered
+        };
+        let solver = find_solver(&query.solver).unwrap();
+        // Combos found so far, and the word-level solutions they stand for
+        let found = Mutex::new((Vec::new(), 0));
+        let searched = panic::catch_unwind(AssertUnwindSafe(|| {
+            solver(keys, &query.options, &|combo| {
+                if cancelled.load(Ordering::Relaxed) {
+                    panic::resume_unwind(Box::new(Stop));
+                }
+                let mut combo = combo;
+                combo.sort();
+                let mut found = found.lock().unwrap();
+                found.0.push(combo);
+                found.1 += combo.iter().map(|key| reprs[key].len()).product::<usize>();
+                if found.1 >= query.limit {
+                    drop(found);
+                    panic::resume_unwind(Box::new(Stop));
+                }
+            })
+        }));
+        let complete = match searched {
+            Ok(()) => true,
+            Err(payload) if payload.is::<Stop>() => false,
+            Err(payload) => panic::resume_unwind(payload),
+        };
+        let (mut combos, count) = found.into_inner().unwrap();
+        combos.sort();
+
+        let solutions: Vec<Vec<&str>> = combos
+            .iter()
+            .flat_map(|combo| {
+                combo
+                    .iter()
+                    .map(|key| reprs[key].iter().map(|word| word.as_str()))
+                    .multi_cartesian_product()
+            })
+            .take(query.limit)
+            .collect();
+        if !query.json {
+            let lines: Vec<String> = solutions.iter().map(|words| words.join(" ")).collect();
+            return Response {
+                status: 200,
+                content_type: "text/plain",
+                body: lines.iter().map(|line| format!("{}\n", line)).collect(),
+            };
+        }
+        let solutions = solutions
+            .iter()
+            .map(|words| self.tags.json_words(words))
+            .join(",");
+        Response::json(
+            200,
+            format!(
+                "{{\"count\":{},\"complete\":{},\"solutions\":[{}]}}",
+                count, complete, solutions
+            ),
+        )
+    }
+}
+
+fn owned_reprs(words: &[String]) -> Reprs {
+    let (reprs, keys) = build_word_representations(words);
+    let reprs = reprs
+        .into_iter()
+        .map(|(key, words)| (key, words.iter().map(|word| word.to_string()).collect()))
+        .collect();
+    (reprs, keys)
+}
+
+struct Query {
+    solver: String,
+    limit: usize,
+    json: bool,
+    options: Options,
+}
+
+/// Method and target of the request line; the headers are read and ignored.
+fn read_request_target(stream: &TcpStream) -> Option<(String, String)> {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).ok()?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next()?.to_string();
+    let target = parts.next()?.to_string();
+    let mut header = String::new();
+    while reader.read_line(&mut header).ok()? > 0 && !header.trim().is_empty() {
+        header.clear();
+    }
+    Some((method, target))
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        504 => "Gateway Timeout",
+        _ => "",
+    }
+}
+
+/// `serve [address]`: serves `words` until killed.
+pub fn serve(words: Vec<String>, tags: Tags, args: &[String], options: &Options) {
+    let addr = args.first().map_or(DEFAULT_ADDR, |addr| addr.as_str());
+    let listener = match TcpListener::bind(addr) {
+        Err(why) => panic!("couldn't listen on {}: {}", addr, why),
+        Ok(listener) => listener,
+    };
+    eprintln!("serving {} words on http://{}", words.len(), addr);
+    Arc::new(Service::new(words, tags, options)).run(listener);
+}
+
+#[cfg(test)]
+fn start_test_service(options: &Options) -> std::net::SocketAddr {
+    start_service_with(test_words(), options)
+}
+
+#[cfg(test)]
+fn start_service_with(words: Vec<String>, options: &Options) -> std::net::SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let service = Arc::new(Service::new(words, Tags::default(), options));
+    thread::spawn(move || service.run(listener));
+    addr
+}
+
+#[cfg(test)]
+fn get(addr: std::net::SocketAddr, target: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", target).unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+    (status, body)
+}
+
+#[test]
+fn test_serve_solutions() {
+    let addr = start_test_service(&Options::default());
+    let (status, body) = get(addr, "/health");
+    assert_eq!(status, 200);
+    assert!(body.starts_with("{\"words\":29,"), "{}", body);
+
+    let (status, all) = get(addr, "/solutions?format=text&solver=brute");
+    assert_eq!(status, 200);
+    let (_, dlx) = get(addr, "/solutions?format=text");
+    assert_eq!(all, dlx);
+    assert!(all.lines().count() > 1);
+
+    let (status, body) = get(addr, "/solutions?include=fjord&include=gucks&limit=1");
+    assert_eq!(status, 200);
+    assert!(
+        body.contains("{\"word\":\"fjord\",\"tags\":[]}"),
+        "{}",
+        body
+    );
+    assert_eq!(body.matches("\"word\"").count(), 5);
+
+    // The search stops at the limit instead of counting every solution
+    let (status, body) = get(addr, "/solutions?limit=1&solver=brute_par");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"complete\":false"), "{}", body);
+    assert_eq!(body.matches("\"word\"").count(), 5);
+    let (_, body) = get(addr, "/solutions?limit=1000");
+    assert!(body.contains("\"complete\":true"), "{}", body);
+
+    let (_, body) = get(addr, "/solutions?exclude=fjord&format=text");
+    assert!(!body.is_empty() && !body.contains("fjord"));
+
+    assert_eq!(get(addr, "/solutions?include=xylyl").0, 400);
+    assert_eq!(get(addr, "/solutions?unused=ab").0, 400);
+    assert_eq!(get(addr, "/solutions?solver=quantum").0, 400);
+    assert_eq!(get(addr, "/nothing").0, 404);
+}
+
+#[test]
+fn test_serve_queries_ignore_run_options() {
+    let expected = get(
+        start_test_service(&Options::default()),
+        "/solutions?format=text&solver=brute_par",
+    );
+    let options = Options {
+        resume: Some(std::env::temp_dir().join("flw-serve-no-such-dir/checkpoint")),
+        shard: crate::shard::Shard::parse("1/2"),
+        sample: Some(1),
+        ..Options::default()
+    };
+    let addr = start_test_service(&options);
+    assert_eq!(
+        get(addr, "/solutions?format=text&solver=brute_par"),
+        expected
+    );
+}
+
+#[test]
+fn test_serve_stops_default_solver() {
+    // A quarter of all 5-letter sets, still far too many solutions to ever find them all
+    let words: Vec<String> = (b'a'..=b'z')
+        .combinations(5)
+        .step_by(4)
+        .map(|letters| String::from_utf8(letters).unwrap())
+        .collect();
+    let options = Options {
+        timeout: Some(1),
+        max_concurrent: Some(1),
+        ..Options::default()
+    };
+    let addr = start_service_with(words, &options);
+    let (status, body) = get(addr, "/solutions?limit=1");
+    assert_eq!(status, 200);
+    assert!(body.contains("\"complete\":false"), "{}", body);
+
+    // The timed out search gives its permit back once it sees it was cancelled
+    assert_eq!(get(addr, "/solutions?limit=1000000000").0, 504);
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(get(addr, "/solutions?limit=1").0, 200);
+}
+
+#[test]
+fn test_serve_concurrency_limit() {
+    let options = Options {
+        max_concurrent: Some(0),
+        ..Options::default()
+    };
+    let addr = start_test_service(&options);
+    assert_eq!(get(addr, "/health").0, 200);
+    assert_eq!(get(addr, "/solutions").0, 503);
+}
+
+#[test]
+fn test_percent_decode() {
+    assert_eq!(percent_decode("fj%6Frd"), "fjord");
+    assert_eq!(percent_decode("a+b%2"), "a b%2");
+}
@@ -0,0 +1,226 @@
+// --shard i/n and merge: splitting one search across processes and putting it back together.
+//
+// The top-level branches of a search are numbered (the first key's position for brute and
+// brute_par, the first pair's first key for smart_brute_par, the row picked in the first chosen
+// column for DLX) and shard i takes the ones with number % n == i, which spreads the big early
+// branches over all shards. Each shard's output ends with a `# shard i/n done` line, so merge can
+// tell a complete set of outputs from one that is missing a shard or was cut short.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+use crate::options::Options;
+
+/// Solvers that honor --shard
+pub const SHARDED_SOLVERS: &[&str] = &["brute", "brute_par", "smart_brute_par", "dlx"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Shard {
+    /// `i/n`, with 0 <= i < n
+    pub fn parse(spec: &str) -> Option<Self> {
+        let (index, count) = spec.split_once('/')?;
+        let shard = Shard {
+            index: index.parse().ok()?,
+            count: count.parse().ok()?,
+        };
+        (shard.index < shard.count).then_some(shard)
+    }
+
+    pub fn owns(&self, branch: usize) -> bool {
+        branch % self.count == self.index
+    }
+
+    pub fn trailer(&self) -> String {
+        format!("# shard {}/{} done", self.index, self.count)
+    }
+}
+
+/// Whether this process searches top-level branch `branch`.
+pub fn owns_branch(options: &Options, branch: usize) -> bool {
+    options.shard.is_none_or(|shard| shard.owns(branch))
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Merged {
+    /// Every solution once, as its words sorted and joined by spaces
+    pub solutions: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+/// Combines the text outputs of the shards of one search, given as (name, contents).
+pub fn merge_outputs(outputs: &[(String, String)], words: &HashSet<&str>) -> Merged {
+    let mut merged = Merged::default();
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut shards: HashMap<usize, &str> = HashMap::new();
+    let mut counts = HashSet::new();
+    for (name, text) in outputs {
+        let mut trailer = None;
+        for line in text.lines() {
+            if let Some(spec) = line
+                .strip_prefix("# shard ")
+                .and_then(|rest| rest.strip_suffix(" done"))
+            {
+                trailer = Shard::parse(spec);
+                continue;
+            }
+            let mut solution: Vec<&str> = line.split_whitespace().collect();
+            if solution.len() != 5 {
+                continue; // counts and other reports
+            }
+            if trailer.is_some() {
+                merged
+                    .problems
+                    .push(format!("{}: solutions after the trailer", name));
+            }
+            for word in solution.iter().filter(|word| !words.contains(*word)) {
+                merged
+                    .problems
+                    .push(format!("{}: {} is not in the word list", name, word));
+            }
+            solution.sort();
+            let solution = solution.join(" ");
+            match seen.get(&solution) {
+                Some(first) => merged.problems.push(format!(
+                    "duplicate solution {} in {} and {}",
+                    solution, first, name
+                )),
+                None => {
+                    seen.insert(solution.clone(), name);
+                    merged.solutions.push(solution);
+                }
+            }
+        }
+        match trailer {
+            None => merged
+                .problems
+                .push(format!("{}: no shard trailer, incomplete", name)),
+            Some(shard) => {
+                counts.insert(shard.count);
+                if let Some(other) = shards.insert(shard.index, name) {
+                    merged.problems.push(format!(
+                        "shard {} is in both {} and {}",
+                        shard.index, other, name
+                    ));
+                }
+            }
+        }
+    }
+    match counts.len() {
+        0 => {}
+        1 => {
+            let count = *counts.iter().next().unwrap();
+            for index in (0..count).filter(|index| !shards.contains_key(index)) {
+                merged
+                    .problems
+                    .push(format!("shard {}/{} is missing", index, count));
+            }
+        }
+        _ => merged
+            .problems
+            .push(format!("outputs of different shard counts {:?}", counts)),
+    }
+    merged.solutions.sort();
+    merged
+}
+
+/// `merge <output>...`: prints the merged solutions, or panics listing what's wrong with them.
+pub fn merge(words: &[String], paths: &[String]) {
+    let outputs: Vec<(String, String)> = paths
+        .iter()
+        .map(|path| match fs::read_to_string(path) {
+            Err(why) => panic!("couldn't read {}: {}", path, why),
+            Ok(text) => (path.clone(), text),
+        })
+        .collect();
+    let words: HashSet<&str> = words.iter().map(|word| word.as_str()).collect();
+    let merged = merge_outputs(&outputs, &words);
+    if !merged.problems.is_empty() {
+        panic!(
+            "merge found {} problems:\n{}",
+            merged.problems.len(),
+            merged.problems.join("\n")
+        );
+    }
+    for solution in merged.solutions.iter() {
+        println!("{}", solution);
+    }
+    eprintln!(
+        "merged {} solutions from {} shards",
+        merged.solutions.len(),
+        outputs.len()
+    );
+}
+
+#[test]
+fn test_parse_shard() {
+    assert_eq!(Shard::parse("1/4"), Some(Shard { index: 1, count: 4 }));
+    assert_eq!(Shard::parse("4/4"), None);
+    assert_eq!(Shard::parse("1"), None);
+}
+
+#[test]
+fn test_shards_partition_solutions() {
+    use crate::word_reprs::*;
+
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    for name in SHARDED_SOLVERS {
+        let solver = crate::find_solver(name).unwrap();
+        let expected = collect_sorted(solver, &keys);
+        let mut found = Vec::new();
+        for index in 0..3 {
+            let options = Options {
+                shard: Some(Shard { index, count: 3 }),
+                ..Options::default()
+            };
+            found.extend(collect_sorted_with(solver, &keys, &options));
+        }
+        found.sort();
+        assert_eq!(found, expected, "{}", name);
+    }
+}
+
+#[test]
+fn test_merge_outputs() {
+    let words: HashSet<&str> = [
+        "fjord", "gucks", "nymph", "vibex", "waltz", "glent", "jumby",
+    ]
+    .into_iter()
+    .collect();
+    let output = |name: &str, text: &str| (name.to_string(), text.to_string());
+    let merged = merge_outputs(
+        &[
+            output("a", "fjord gucks nymph vibex waltz \n# shard 0/2 done\n"),
+            output("b", "Solutions count: 0\n# shard 1/2 done\n"),
+        ],
+        &words,
+    );
+    assert_eq!(merged.problems, Vec::<String>::new());
+    assert_eq!(merged.solutions, vec!["fjord gucks nymph vibex waltz"]);
+
+    let merged = merge_outputs(
+        &[
+            output("a", "fjord gucks nymph vibex waltz\n# shard 0/3 done\n"),
+            output(
+                "b",
+                "waltz vibex nymph gucks fjord\nxylyl gucks nymph vibex waltz\n",
+            ),
+        ],
+        &words,
+    );
+    assert_eq!(
+        merged.problems,
+        vec![
+            "duplicate solution fjord gucks nymph vibex waltz in a and b",
+            "b: xylyl is not in the word list",
+            "b: no shard trailer, incomplete",
+            "shard 1/3 is missing",
+            "shard 2/3 is missing",
+        ]
+    );
+}
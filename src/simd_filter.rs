@@ -0,0 +1,213 @@
+// Batched "which keys are disjoint with this mask" filtering.
+//
+// The brute-force loops spend most of their time testing `key & combo != 0` one key at a time.
+// Here keys are tested 8 at a time and the survivors are compacted into an output buffer,
+// using AVX2 when the CPU has it (detected at runtime) and a portable batched loop otherwise.
+
+/// Appends every key from `keys` that has no bits in common with `mask` to `out`.
+pub fn filter_disjoint(keys: &[u32], mask: u32, out: &mut Vec<u32>) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: filter_avx2 only needs AVX2, which the CPU was just checked to have
+            unsafe { filter_avx2::<false>(keys, mask, out) };
+            return;
+        }
+    }
+    filter_batched::<false>(keys, mask, out);
+}
+
+/// Like `filter_disjoint`, but appends positions within `keys` instead of the keys themselves.
+pub fn filter_disjoint_positions(keys: &[u32], mask: u32, out: &mut Vec<u32>) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if is_x86_feature_detected!("avx2") {
+            // SAFETY: filter_avx2 only needs AVX2, which the CPU was just checked to have
+            unsafe { filter_avx2::<true>(keys, mask, out) };
+            return;
+        }
+    }
+    filter_batched::<true>(keys, mask, out);
+}
+
+/// Reference implementation, one key at a time.
+#[cfg(test)]
+pub fn filter_disjoint_scalar(keys: &[u32], mask: u32, out: &mut Vec<u32>) {
+    for key in keys {
+        if key & mask == 0 {
+            out.push(*key);
+        }
+    }
+}
+
+const BATCH: usize = 8;
+
+fn filter_batched<const POSITIONS: bool>(keys: &[u32], mask: u32, out: &mut Vec<u32>) {
+    for (batch_no, batch) in keys.chunks(BATCH).enumerate() {
+        // Branch-free test of the whole batch first, so that it can be auto-vectorized
+        let mut hits = 0u32;
+        for (i, key) in batch.iter().enumerate() {
+            hits |= ((key & mask == 0) as u32) << i;
+        }
+        while hits != 0 {
+            let i = hits.trailing_zeros() as usize;
+            out.push(if POSITIONS {
+                (batch_no * BATCH + i) as u32
+            } else {
+                batch[i]
+            });
+            hits &= hits - 1;
+        }
+    }
+}
+
+/// COMPACT[hits] lists the lanes set in `hits` first, for _mm256_permutevar8x32_epi32
+#[cfg(target_arch = "x86_64")]
+const COMPACT: [[u32; BATCH]; 1 << BATCH] = build_compact_table();
+
+#[cfg(target_arch = "x86_64")]
+const fn build_compact_table() -> [[u32; BATCH]; 1 << BATCH] {
+    let mut table = [[0u32; BATCH]; 1 << BATCH];
+    let mut hits = 0;
+    while hits < (1 << BATCH) {
+        let mut lane = 0;
+        let mut n = 0;
+        while lane < BATCH {
+            if hits & (1 << lane) != 0 {
+                table[hits][n] = lane as u32;
+                n += 1;
+            }
+            lane += 1;
+        }
+        hits += 1;
+    }
+    table
+}
+
+/// # Safety
+///
+/// The CPU must support AVX2.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn filter_avx2<const POSITIONS: bool>(keys: &[u32], mask: u32, out: &mut Vec<u32>) {
+    use std::arch::x86_64::*;
+
+    let batches = keys.chunks_exact(BATCH);
+    let remainder = batches.remainder();
+    let remainder_start = keys.len() - remainder.len();
+
+    // Every batch stores all 8 lanes and only then advances by the survivor count
+    out.reserve(keys.len() + BATCH);
+    let dst = out.as_mut_ptr();
+    let mut len = out.len();
+
+    let mask_v = _mm256_set1_epi32(mask as i32);
+    let zero = _mm256_setzero_si256();
+    let step = _mm256_set1_epi32(BATCH as i32);
+    let mut positions = _mm256_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7);
+    for batch in batches {
+        // SAFETY: chunks_exact makes `batch` exactly 8 u32s, and loadu has no alignment
+        // requirement
+        let v = _mm256_loadu_si256(batch.as_ptr() as *const __m256i);
+        let disjoint = _mm256_cmpeq_epi32(_mm256_and_si256(v, mask_v), zero);
+        let hits = _mm256_movemask_ps(_mm256_castsi256_ps(disjoint)) as usize;
+        // SAFETY: `hits` is an 8-bit movemask, so it indexes COMPACT, whose rows are 8 u32s
+        let perm = _mm256_loadu_si256(COMPACT[hits].as_ptr() as *const __m256i);
+        let src = if POSITIONS { positions } else { v };
+        // SAFETY: at most one lane per key has been kept so far, so `len + 8` is within the
+        // `keys.len() + BATCH` spare capacity reserved above
+        _mm256_storeu_si256(
+            dst.add(len) as *mut __m256i,
+            _mm256_permutevar8x32_epi32(src, perm),
+        );
+        len += hits.count_ones() as usize;
+        positions = _mm256_add_epi32(positions, step);
+    }
+    // SAFETY: the first `len` elements were either already in `out` or written by the stores
+    // above, and `len` is within the reserved capacity
+    out.set_len(len);
+
+    for (i, key) in remainder.iter().enumerate() {
+        if key & mask == 0 {
+            out.push(if POSITIONS {
+                (remainder_start + i) as u32
+            } else {
+                *key
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+fn pseudo_random_keys(n: usize) -> Vec<u32> {
+    let mut state = 0x2545f491u32;
+    (0..n)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state & 0x3ffffff
+        })
+        .collect()
+}
+
+#[test]
+fn test_filter_matches_scalar() {
+    let keys = pseudo_random_keys(1003);
+    for mask in [0u32, 1, 0b1011, 0x155, 0x3ffffff] {
+        for start in [0, 1, 7, 500, 1003] {
+            let mut expected = Vec::new();
+            filter_disjoint_scalar(&keys[start..], mask, &mut expected);
+
+            let mut found = vec![42];
+            filter_disjoint(&keys[start..], mask, &mut found);
+            assert_eq!(found[0], 42);
+            assert_eq!(found[1..], expected[..]);
+
+            let mut batched = Vec::new();
+            filter_batched::<false>(&keys[start..], mask, &mut batched);
+            assert_eq!(batched, expected);
+        }
+    }
+}
+
+#[test]
+fn test_filter_positions_matches_scalar() {
+    let keys = pseudo_random_keys(517);
+    let mask = 0b10_0000_0001_0000_1000_0010;
+    let expected: Vec<u32> = keys
+        .iter()
+        .enumerate()
+        .filter(|(_, key)| *key & mask == 0)
+        .map(|(i, _)| i as u32)
+        .collect();
+
+    let mut found = Vec::new();
+    filter_disjoint_positions(&keys, mask, &mut found);
+    assert_eq!(found, expected);
+
+    let mut batched = Vec::new();
+    filter_batched::<true>(&keys, mask, &mut batched);
+    assert_eq!(batched, expected);
+}
+
+#[test]
+fn test_simd_solvers_match_scalar() {
+    use crate::word_reprs::*;
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+
+    let expected = collect_sorted(crate::backtracking_brute::backtracking_brute_keys, &keys);
+    assert!(!expected.is_empty());
+    assert_eq!(
+        collect_sorted(
+            crate::backtracking_brute::backtracking_brute_simd_keys,
+            &keys
+        ),
+        expected
+    );
+    assert_eq!(
+        collect_sorted(crate::smart_brute::smart_brute_simd_keys, &keys),
+        expected
+    );
+}
@@ -1,61 +1,429 @@
-use itertools::Itertools;
 use rayon::prelude::*;
 use rustc_hash::FxHashMap;
-use std::collections::HashMap;
+use std::fs::File;
 use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
+use crate::checkpoint::Checkpoint;
+use crate::compat_index::*;
+use crate::constraints::Constraints;
+use crate::options::Options;
+use crate::shard::owns_branch;
+use crate::simd_filter::*;
 use crate::word_reprs::*;
 
-pub fn smart_brute(words: Vec<String>) {
+// (combo_key, combo, positions)
+type KeyPair = (u32, [u32; 2], [usize; 2]);
+// combo_key -> [(key1, key2), (pos1, pos2)]
+type KeyPairMap = FxHashMap<u32, Vec<([u32; 2], [usize; 2])>>;
+
+pub fn smart_brute(words: Vec<String>, options: &Options) {
+    let (reprs, keys) = build_word_representations(&words);
+    smart_brute_keys(&keys, options, &|combo| print_combo(combo, &reprs));
+}
+
+pub fn smart_brute_simd(words: Vec<String>, options: &Options) {
     let (reprs, keys) = build_word_representations(&words);
-    let keys2vec = build_unique_pairs(&keys);
+    smart_brute_simd_keys(&keys, options, &|combo| print_combo(combo, &reprs));
+}
+
+/// Pairs are read off a `CompatIndex`, and the third word's candidates are the intersection of
+/// the first two words' compatibility rows.
+pub fn smart_brute_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let keys = &constraints.candidate_keys(keys);
+    let emit = &constraints.sink(emit);
+    let index = CompatIndex::build(keys);
+    if options.verbose {
+        index.report();
+    }
+    let checkpoint = Checkpoint::open(options, "smart_brute_par", keys);
+    let skippable = constraints.skippable();
+    search_pairs(
+        keys,
+        build_unique_pairs(keys, &index),
+        &index,
+        checkpoint,
+        skippable,
+        options,
+        emit,
+    );
+}
+
+/// Same as `smart_brute_keys`, starting from a pair table stored in an index (see `pair_table`).
+/// The table only fits the keys it was built from, so constraints that change the candidate keys
+/// fall back to building a new one.
+pub fn smart_brute_with_pairs(
+    keys: &[u32],
+    pairs: &[[u32; 3]],
+    options: &Options,
+    emit: &ComboSink,
+) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    if constraints.candidate_keys(keys) != keys {
+        smart_brute_keys(keys, options, emit);
+        return;
+    }
+    let keys2vec = pairs
+        .iter()
+        .map(|&[combo_key, pos1, pos2]| {
+            let (pos1, pos2) = (pos1 as usize, pos2 as usize);
+            (combo_key, [keys[pos1], keys[pos2]], [pos1, pos2])
+        })
+        .collect();
+    let index = CompatIndex::build(keys);
+    if options.verbose {
+        index.report();
+    }
+    let checkpoint = Checkpoint::open(options, "smart_brute_par", keys);
+    let skippable = constraints.skippable();
+    search_pairs(
+        keys,
+        keys2vec,
+        &index,
+        checkpoint,
+        skippable,
+        options,
+        &constraints.sink(emit),
+    );
+}
+
+/// Disjoint key pairs as (combo_key, pos1, pos2), in the order `smart_brute_keys` builds them.
+pub fn pair_table(keys: &[u32]) -> Vec<[u32; 3]> {
+    build_unique_pairs(keys, &CompatIndex::build(keys))
+        .into_iter()
+        .map(|(combo_key, _, [pos1, pos2])| [combo_key, pos1 as u32, pos2 as u32])
+        .collect()
+}
+
+/// The pairs of each first key make up a top-level branch, for --resume and --shard.
+/// Only combos that leave out a letter of `skippable` are searched.
+fn search_pairs(
+    keys: &[u32],
+    keys2vec: Vec<KeyPair>,
+    index: &CompatIndex,
+    checkpoint: Option<Checkpoint>,
+    skippable: u32,
+    options: &Options,
+    emit: &ComboSink,
+) {
     // build map (combo_key_2) -> [(key1, key2), (pos1, pos2)]
-    let mut keys2map: FxHashMap<u32, Vec<([u32; 2], [usize; 2])>> = FxHashMap::default();
+    let start = Instant::now();
+    let mut keys2map: KeyPairMap = FxHashMap::default();
+    for &(combo_key, key_arr, pos_arr) in keys2vec.iter() {
+        keys2map
+            .entry(combo_key)
+            .or_default()
+            .push((key_arr, pos_arr));
+    }
+    if options.verbose {
+        report_pair_tables(&keys2vec, &keys2map, start.elapsed());
+    }
+    // Only the first pairs are sharded, the later words come from all of them
+    let keys2vec: Vec<KeyPair> = match options.shard {
+        Some(_) => keys2vec
+            .iter()
+            .filter(|(_, _, [pos1, _])| owns_branch(options, *pos1))
+            .copied()
+            .collect(),
+        None => keys2vec,
+    };
+    let Some(checkpoint) = checkpoint else {
+        build_unique_triplets(keys, &keys2vec, &keys2map, index, skippable, emit);
+        return;
+    };
+    let branches: Vec<&[KeyPair]> = keys2vec.chunk_by(|a, b| a.2[0] == b.2[0]).collect();
+    branches
+        .into_par_iter()
+        .filter(|pairs| !checkpoint.is_done(pairs[0].2[0]))
+        .for_each(|pairs| {
+            checkpoint.run_branch(pairs[0].2[0], emit, |sink| {
+                build_unique_triplets(keys, pairs, &keys2map, index, skippable, sink)
+            })
+        });
+}
+
+/// Same as `smart_brute_keys`, with the pair and triplet candidates found by `simd_filter`.
+pub fn smart_brute_simd_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let keys = &constraints.candidate_keys(keys);
+    let emit = &constraints.sink(emit);
+    let keys2vec = build_unique_pairs_simd(keys);
+    let mut keys2map: KeyPairMap = FxHashMap::default();
     for &(combo_key, key_arr, pos_arr) in keys2vec.iter() {
-        if keys2map.contains_key(&combo_key) {
-            keys2map
-                .get_mut(&combo_key)
-                .unwrap()
-                .push((key_arr, pos_arr));
-        } else {
-            keys2map.insert(combo_key, vec![(key_arr, pos_arr)]);
+        keys2map
+            .entry(combo_key)
+            .or_default()
+            .push((key_arr, pos_arr));
+    }
+    build_unique_triplets_simd(keys, &keys2vec, &keys2map, constraints.skippable(), emit);
+}
+
+/// Memory budget for the pair table when --mem-cap is not given
+const DEFAULT_MEM_CAP: usize = 1024 * 1024 * 1024;
+/// Rough cost of one pair in the map: the entry itself plus its share of the map overhead
+const PAIR_ENTRY_BYTES: usize = 64;
+const LETTERS: u32 = 26;
+const PARTITIONS: usize = (LETTERS * LETTERS) as usize;
+
+/// Same search as `smart_brute_keys`, but the (key4, key5) pair table is never held in full.
+/// It is partitioned by the two lowest set bits of the pairs' combo keys (the lowest bit alone
+/// puts most pairs in the 'a' partition), and consecutive partitions
+/// are grouped so that each group stays under `options.mem_cap`. Groups are processed one after
+/// another, each with a full pass over the (key1, key2, key3) triplets, which only look up the
+/// two-word combo keys that fall into the current group.
+///
+/// With `options.spill_dir`, pairs are written to one file per group in a single pass, instead of
+/// being regenerated from the keys for every group.
+pub fn smart_brute_bounded_keys(keys: &[u32], options: &Options, emit: &ComboSink) {
+    let constraints = Constraints::new(options);
+    if !constraints.feasible() {
+        return;
+    }
+    let keys = &constraints.candidate_keys(keys);
+    let emit = &constraints.sink(emit);
+    let skippable = constraints.skippable();
+    let mem_cap = options.mem_cap.unwrap_or(DEFAULT_MEM_CAP);
+
+    let mut partition_sizes = vec![0usize; PARTITIONS];
+    for_each_pair(keys, |combo_key, _, _| {
+        partition_sizes[partition_of(combo_key)] += 1;
+    });
+    let groups = group_partitions(&partition_sizes, mem_cap);
+    let largest_group = groups
+        .iter()
+        .map(|group| partition_sizes[group.clone()].iter().sum::<usize>())
+        .max()
+        .unwrap_or(0);
+    eprintln!(
+        "pair tables: {} pairs in {} groups, largest group {:.2} MiB (cap {:.2} MiB)",
+        partition_sizes.iter().sum::<usize>(),
+        groups.len(),
+        (largest_group * PAIR_ENTRY_BYTES) as f64 / (1024.0 * 1024.0),
+        mem_cap as f64 / (1024.0 * 1024.0)
+    );
+
+    let spill = options
+        .spill_dir
+        .as_ref()
+        .map(|dir| spill_pairs(keys, &groups, dir));
+
+    for (group_no, group) in groups.iter().enumerate() {
+        let in_group = |combo_key: u32| group.contains(&partition_of(combo_key));
+        let mut keys2map: KeyPairMap = FxHashMap::default();
+        match &spill {
+            Some(files) => read_spilled_pairs(&files[group_no], |combo_key, key_arr, pos_arr| {
+                keys2map
+                    .entry(combo_key)
+                    .or_default()
+                    .push((key_arr, pos_arr));
+            }),
+            None => for_each_pair(keys, |combo_key, key_arr, pos_arr| {
+                if in_group(combo_key) {
+                    keys2map
+                        .entry(combo_key)
+                        .or_default()
+                        .push((key_arr, pos_arr));
+                }
+            }),
+        }
+
+        (0..keys.len()).into_par_iter().for_each(|pos1| {
+            let key1 = keys[pos1];
+            for (pos2, key2) in keys.iter().enumerate().skip(pos1 + 1) {
+                if key1 & key2 != 0 {
+                    continue;
+                }
+                let combo_key = key1 | key2;
+                for (pos3, key3) in keys.iter().enumerate().skip(pos2 + 1) {
+                    if combo_key & key3 != 0 {
+                        continue;
+                    }
+                    for two_word_combo in
+                        get_matching_two_word_combo_keys(combo_key | key3, skippable)
+                    {
+                        if two_word_combo == 0 || !in_group(two_word_combo) {
+                            continue;
+                        }
+                        if let Some(v) = keys2map.get(&two_word_combo) {
+                            for ([key4, key5], [pos4, _pos5]) in v.iter() {
+                                if *pos4 <= pos3 {
+                                    continue;
+                                }
+                                emit([key1, *key2, *key3, *key4, *key5])
+                            }
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    if let Some(files) = spill {
+        for file in files {
+            let _ = std::fs::remove_file(file);
         }
     }
-    build_unique_triplets(&keys, &keys2vec, &keys2map, &reprs);
 }
 
-fn build_unique_pairs(keys: &Vec<u32>) -> Vec<(u32, [u32; 2], [usize; 2])> {
-    // (combo_key, combo, positions)
-    let mut res: Vec<(u32, [u32; 2], [usize; 2])> = Vec::new();
-    for (pos1, key1) in keys.into_iter().enumerate() {
-        for (pos2, key2) in keys.into_iter().enumerate().skip(pos1) {
-            if key1 & key2 != 0 {
-                continue;
+fn for_each_pair<F: FnMut(u32, [u32; 2], [usize; 2])>(keys: &[u32], mut f: F) {
+    for (pos1, key1) in keys.iter().enumerate() {
+        for (pos2, key2) in keys.iter().enumerate().skip(pos1 + 1) {
+            if key1 & key2 == 0 {
+                f(key1 | key2, [*key1, *key2], [pos1, pos2]);
             }
-            res.push((key1 | key2, [*key1, *key2], [pos1, pos2]));
+        }
+    }
+}
+
+/// Index of the partition a combo key belongs to, from its two lowest set bits.
+fn partition_of(combo_key: u32) -> usize {
+    let lowest = combo_key.trailing_zeros();
+    let second = (combo_key & (combo_key - 1)).trailing_zeros();
+    (lowest * LETTERS + second) as usize
+}
+
+/// Groups consecutive partitions so that each group's pairs fit in `mem_cap`.
+/// A single partition larger than the cap still gets a group of its own.
+fn group_partitions(partition_sizes: &[usize], mem_cap: usize) -> Vec<Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    let mut group_bytes = 0;
+    for (partition, size) in partition_sizes.iter().enumerate() {
+        let bytes = size * PAIR_ENTRY_BYTES;
+        if bytes > mem_cap {
+            eprintln!(
+                "pair partition {} needs {:.2} MiB, over the memory cap",
+                partition,
+                bytes as f64 / (1024.0 * 1024.0)
+            );
+        }
+        if group_bytes + bytes > mem_cap && partition > start {
+            groups.push(start..partition);
+            start = partition;
+            group_bytes = 0;
+        }
+        group_bytes += bytes;
+    }
+    groups.push(start..partition_sizes.len());
+    groups
+}
+
+// Spilled pair record: combo_key, key4, key5, pos4, pos5 as little endian u32s
+const SPILL_RECORD_BYTES: usize = 20;
+
+fn spill_pairs(keys: &[u32], groups: &[Range<usize>], dir: &Path) -> Vec<PathBuf> {
+    let paths: Vec<PathBuf> = (0..groups.len())
+        .map(|group_no| dir.join(format!("pairs-{}-{}.bin", std::process::id(), group_no)))
+        .collect();
+    let mut writers: Vec<BufWriter<File>> = paths
+        .iter()
+        .map(|path| match File::create(path) {
+            Err(why) => panic!("couldn't create {}: {}", path.display(), why),
+            Ok(file) => BufWriter::new(file),
+        })
+        .collect();
+    let mut group_of_partition = vec![0usize; PARTITIONS];
+    for (group_no, group) in groups.iter().enumerate() {
+        for partition in group.clone() {
+            group_of_partition[partition] = group_no;
+        }
+    }
+    for_each_pair(keys, |combo_key, [key4, key5], [pos4, pos5]| {
+        let mut record = [0u8; SPILL_RECORD_BYTES];
+        for (i, value) in [combo_key, key4, key5, pos4 as u32, pos5 as u32]
+            .iter()
+            .enumerate()
+        {
+            record[i * 4..(i + 1) * 4].copy_from_slice(&value.to_le_bytes());
+        }
+        let writer = &mut writers[group_of_partition[partition_of(combo_key)]];
+        if let Err(why) = writer.write_all(&record) {
+            panic!("couldn't spill pairs: {}", why);
+        }
+    });
+    for mut writer in writers {
+        if let Err(why) = writer.flush() {
+            panic!("couldn't spill pairs: {}", why);
+        }
+    }
+    paths
+}
+
+fn read_spilled_pairs<F: FnMut(u32, [u32; 2], [usize; 2])>(path: &Path, mut f: F) {
+    let mut reader = match File::open(path) {
+        Err(why) => panic!("couldn't open {}: {}", path.display(), why),
+        Ok(file) => BufReader::new(file),
+    };
+    let mut record = [0u8; SPILL_RECORD_BYTES];
+    while reader.read_exact(&mut record).is_ok() {
+        let value = |i: usize| u32::from_le_bytes(record[i * 4..(i + 1) * 4].try_into().unwrap());
+        f(
+            value(0),
+            [value(1), value(2)],
+            [value(3) as usize, value(4) as usize],
+        );
+    }
+}
+
+fn report_pair_tables(keys2vec: &[KeyPair], keys2map: &KeyPairMap, build_time: Duration) {
+    let vec_bytes = std::mem::size_of_val(keys2vec);
+    let map_bytes = keys2map.capacity() * std::mem::size_of::<(u32, Vec<([u32; 2], [usize; 2])>)>()
+        + keys2map
+            .values()
+            .map(|v| v.capacity() * std::mem::size_of::<([u32; 2], [usize; 2])>())
+            .sum::<usize>();
+    eprintln!(
+        "pair tables: {} pairs, {} combo keys, {:.2} MiB, built in {:.1} ms",
+        keys2vec.len(),
+        keys2map.len(),
+        (vec_bytes + map_bytes) as f64 / (1024.0 * 1024.0),
+        build_time.as_secs_f64() * 1000.0
+    );
+}
+
+fn build_unique_pairs(keys: &[u32], index: &CompatIndex) -> Vec<KeyPair> {
+    let mut res: Vec<KeyPair> = Vec::with_capacity(index.pair_count());
+    for (pos1, key1) in keys.iter().enumerate() {
+        for pos2 in index.later(pos1) {
+            let key2 = keys[pos2];
+            res.push((key1 | key2, [*key1, key2], [pos1, pos2]));
         }
     }
     res
 }
 
 fn build_unique_triplets(
-    keys: &Vec<u32>,
-    keys2: &Vec<(u32, [u32; 2], [usize; 2])>,
-    keys2map: &FxHashMap<u32, Vec<([u32; 2], [usize; 2])>>,
-    reprs: &HashMap<u32, Vec<&str>>,
+    keys: &[u32],
+    keys2: &[KeyPair],
+    keys2map: &KeyPairMap,
+    index: &CompatIndex,
+    skippable: u32,
+    emit: &ComboSink,
 ) {
-    keys2
-        .into_par_iter()
-        .for_each(|(combo_key, [key1, key2], [_pos1, pos2])| {
-            for (pos3, key3) in keys.into_iter().enumerate().skip(*pos2) {
-                if combo_key & key3 != 0 {
-                    continue;
-                }
+    keys2.into_par_iter().for_each_init(
+        || vec![0u64; index.words_per_row()],
+        |candidates: &mut Vec<u64>, (combo_key, [key1, key2], [pos1, pos2])| {
+            intersect_rows(index.row(*pos1), index.row(*pos2), pos2 / 64, candidates);
+            for pos3 in set_bits(candidates, pos2 / 64) {
+                let key3 = &keys[pos3];
                 let triple_combo = combo_key | key3;
                 // We have a unique triplet.
                 // Calculate the 26 possible two-word combo_keys that will match with this triplet
                 let possible_two_word_combo_keys: [u32; 11] =
-                    get_matching_two_word_combo_keys(triple_combo);
+                    get_matching_two_word_combo_keys(triple_combo, skippable);
                 for two_word_combo in possible_two_word_combo_keys {
                     match keys2map.get(&two_word_combo) {
                         None => (),
@@ -65,22 +433,72 @@ fn build_unique_triplets(
                                     // We need strict ordering here! pos1 < pos2 < pos3 < pos4 < pos5
                                     continue;
                                 }
-                                combo_pretty_print([*key1, *key2, *key3, *key4, *key5], reprs)
+                                emit([*key1, *key2, *key3, *key4, *key5])
                             }
                         }
                     }
                 }
             }
-        });
+        },
+    );
+}
+
+fn build_unique_pairs_simd(keys: &[u32]) -> Vec<KeyPair> {
+    let mut res: Vec<KeyPair> = Vec::new();
+    let mut positions: Vec<u32> = Vec::with_capacity(keys.len() + 8);
+    for (pos1, key1) in keys.iter().enumerate() {
+        positions.clear();
+        filter_disjoint_positions(&keys[pos1..], *key1, &mut positions);
+        for &offset in positions.iter() {
+            let pos2 = pos1 + offset as usize;
+            let key2 = keys[pos2];
+            res.push((key1 | key2, [*key1, key2], [pos1, pos2]));
+        }
+    }
+    res
+}
+
+fn build_unique_triplets_simd(
+    keys: &[u32],
+    keys2: &[KeyPair],
+    keys2map: &KeyPairMap,
+    skippable: u32,
+    emit: &ComboSink,
+) {
+    keys2.into_par_iter().for_each_init(
+        || Vec::with_capacity(keys.len() + 8),
+        |positions: &mut Vec<u32>, (combo_key, [key1, key2], [_pos1, pos2])| {
+            positions.clear();
+            filter_disjoint_positions(&keys[*pos2..], *combo_key, positions);
+            for &offset in positions.iter() {
+                let pos3 = pos2 + offset as usize;
+                let key3 = keys[pos3];
+                let triple_combo = combo_key | key3;
+                for two_word_combo in get_matching_two_word_combo_keys(triple_combo, skippable) {
+                    if let Some(v) = keys2map.get(&two_word_combo) {
+                        for ([key4, key5], [pos4, _pos5]) in v.iter() {
+                            if *pos4 <= pos3 {
+                                // We need strict ordering here! pos1 < pos2 < pos3 < pos4 < pos5
+                                continue;
+                            }
+                            emit([*key1, *key2, key3, *key4, *key5])
+                        }
+                    }
+                }
+            }
+        },
+    );
 }
 
-fn get_matching_two_word_combo_keys(triple_combo: u32) -> [u32; 11] {
+/// The letters left by `triple_combo` minus one of them, for each letter of `skippable` it
+/// leaves; unused slots stay 0, which no pair has as its combo key.
+fn get_matching_two_word_combo_keys(triple_combo: u32, skippable: u32) -> [u32; 11] {
     let mut res = [0u32; 11];
     let full = !(0xff << 26) ^ triple_combo;
     let mut index: usize = 0;
     for i in 0..26u32 {
         let new = full & !(1 << i);
-        if new != full {
+        if new != full && skippable & (1 << i) != 0 {
             res[index] = new;
             index += 1;
         }
@@ -88,17 +506,38 @@ fn get_matching_two_word_combo_keys(triple_combo: u32) -> [u32; 11] {
     res
 }
 
-fn combo_pretty_print(combo: [u32; 5], reprs: &HashMap<u32, Vec<&str>>) {
-    for word_combo in combo
-        .iter()
-        .map(|key| reprs.get(key).unwrap())
-        .multi_cartesian_product()
-    {
-        let word_combo = word_combo.into_iter().fold(String::new(), |mut acc, word| {
-            acc.push_str(word);
-            acc.push_str(" ");
-            acc
-        });
-        let _ = writeln!(std::io::stdout(), "{}", word_combo);
-    }
+#[test]
+fn test_group_partitions() {
+    let sizes = [10, 10, 0, 30, 5];
+    let cap = 20 * PAIR_ENTRY_BYTES;
+    assert_eq!(group_partitions(&sizes, cap), vec![0..3, 3..4, 4..5]);
+    assert_eq!(group_partitions(&sizes, usize::MAX), vec![0..5]);
+}
+
+#[test]
+fn test_bounded_matches_smart_brute() {
+    let words = test_words();
+    let (_reprs, keys) = build_word_representations(&words);
+    let expected = collect_sorted(smart_brute_keys, &keys);
+    assert!(!expected.is_empty());
+
+    // A tiny cap forces one group per non-empty partition
+    let mut options = Options {
+        mem_cap: Some(PAIR_ENTRY_BYTES),
+        ..Options::default()
+    };
+    assert_eq!(
+        collect_sorted_with(smart_brute_bounded_keys, &keys, &options),
+        expected
+    );
+
+    let spill_dir = std::env::temp_dir().join(format!("flw-spill-{}", std::process::id()));
+    std::fs::create_dir_all(&spill_dir).unwrap();
+    options.spill_dir = Some(spill_dir.clone());
+    assert_eq!(
+        collect_sorted_with(smart_brute_bounded_keys, &keys, &options),
+        expected
+    );
+    assert_eq!(std::fs::read_dir(&spill_dir).unwrap().count(), 0);
+    std::fs::remove_dir(&spill_dir).unwrap();
 }
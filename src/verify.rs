@@ -0,0 +1,340 @@
+// verify: checks a file of solutions, from this program or another one, against the word list.
+//
+// Lines may be plain words separated by spaces, CSV, or JSON lines; in JSON the words are the
+// values of "word" keys if there are any and every string value otherwise, which covers both
+// --format json output and plain arrays. Blank lines, `#` comments and `Solutions count` lines
+// are skipped, and so is a first line with no word from the list, like a CSV header. Each
+// solution must have five words from the list with no letter in common, so that exactly one
+// letter is left unused, and the valid ones are compared with a fresh run of the solver to find
+// the missing and extra ones.
+
+use std::collections::HashSet;
+use std::fs;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::sync::Mutex;
+
+use itertools::Itertools;
+
+use crate::options::Options;
+use crate::word_reprs::{build_word_representations, get_repr};
+use crate::{find_solver, SolverFn};
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    pub lines: usize,
+    pub valid: usize,
+    pub problems: Vec<String>,
+    /// Solutions the solver finds that the file doesn't have, as sorted words joined by spaces
+    pub missing: Vec<String>,
+    /// Valid solutions in the file that the solver doesn't find
+    pub extra: Vec<String>,
+}
+
+impl Report {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty() && self.missing.is_empty() && self.extra.is_empty()
+    }
+}
+
+// String literals of a JSON line, each with whether it's an object key
+fn json_strings(line: &str) -> Vec<(String, bool)> {
+    let mut strings = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '"' {
+            continue;
+        }
+        let mut string = String::new();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => string.push('\n'),
+                    Some('t') => string.push('\t'),
+                    Some('u') => string.push(unicode_escape(&mut chars)),
+                    Some(escaped) => string.push(escaped),
+                    None => {}
+                },
+                ch => string.push(ch),
+            }
+        }
+        while chars.next_if(|ch| ch.is_whitespace()).is_some() {}
+        let is_key = chars.peek() == Some(&':');
+        strings.push((string, is_key));
+    }
+    strings
+}
+
+/// The character of a `\uXXXX` escape, after the `\u`; a UTF-16 surrogate pair takes two of them.
+/// Broken escapes become U+FFFD, which no word in the list has.
+fn unicode_escape(chars: &mut Peekable<Chars>) -> char {
+    fn hex4(chars: &mut Peekable<Chars>) -> Option<u32> {
+        (0..4).try_fold(0, |acc, _| {
+            let digit = chars.next_if(|ch| ch.is_ascii_hexdigit())?;
+            Some(acc * 16 + digit.to_digit(16)?)
+        })
+    }
+    let Some(high) = hex4(chars) else {
+        return char::REPLACEMENT_CHARACTER;
+    };
+    if !(0xD800..0xDC00).contains(&high) {
+        return char::from_u32(high).unwrap_or(char::REPLACEMENT_CHARACTER);
+    }
+    if chars.next_if_eq(&'\\').is_none() || chars.next_if_eq(&'u').is_none() {
+        return char::REPLACEMENT_CHARACTER;
+    }
+    match hex4(chars) {
+        Some(low) if (0xDC00..0xE000).contains(&low) => {
+            char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                .unwrap_or(char::REPLACEMENT_CHARACTER)
+        }
+        _ => char::REPLACEMENT_CHARACTER,
+    }
+}
+
+/// The words of one line of a solution file, or None if it isn't a solution.
+pub fn parse_solution_line(line: &str) -> Option<Vec<String>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') || line.starts_with("Solutions count") {
+        return None;
+    }
+    if line.starts_with('{') || line.starts_with('[') {
+        let strings = json_strings(line);
+        let words: Vec<String> = if strings.iter().any(|(s, is_key)| *is_key && s == "word") {
+            strings
+                .iter()
+                .tuple_windows()
+                .filter(|((key, is_key), _)| *is_key && key == "word")
+                .map(|(_, (word, _))| word.clone())
+                .collect()
+        } else {
+            strings
+                .into_iter()
+                .filter(|(_, is_key)| !is_key)
+                .map(|(word, _)| word)
+                .collect()
+        };
+        return Some(words);
+    }
+    let words = if line.contains(',') {
+        line.split(',')
+            .map(|field| field.trim().trim_matches('"').to_string())
+            .filter(|word| !word.is_empty())
+            .collect()
+    } else {
+        line.split_whitespace()
+            .map(|word| word.to_string())
+            .collect()
+    };
+    Some(words)
+}
+
+fn is_5_letters(word: &str) -> bool {
+    word.len() == 5 && word.bytes().all(|byte| byte.is_ascii_lowercase())
+}
+
+/// Checks the solutions in `text` against `words` and the solutions in `expected`.
+pub fn verify_solutions(text: &str, words: &[String], expected: &HashSet<String>) -> Report {
+    let known: HashSet<&str> = words.iter().map(|word| word.as_str()).collect();
+    let mut report = Report::default();
+    let mut seen: HashSet<String> = HashSet::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let Some(solution) = parse_solution_line(line) else {
+            continue;
+        };
+        if report.lines == 0 && !solution.iter().any(|word| known.contains(word.as_str())) {
+            continue; // a header
+        }
+        report.lines += 1;
+        let mut problems = Vec::new();
+        if solution.len() != 5 {
+            problems.push(format!("{} words", solution.len()));
+        }
+        for word in solution
+            .iter()
+            .filter(|word| !known.contains(word.as_str()))
+        {
+            problems.push(format!("{} is not in the word list", word));
+        }
+        // get_repr only takes lowercase letters; a header or an uppercase word is reported as
+        // not in the word list above, and left out of the letter checks
+        let lettered: Vec<&String> = solution.iter().filter(|word| is_5_letters(word)).collect();
+        for (a, b) in lettered.iter().tuple_combinations() {
+            if get_repr(a) & get_repr(b) != 0 {
+                problems.push(format!("{} and {} share letters", a, b));
+            }
+        }
+        if lettered.len() == solution.len() {
+            let letters = solution.iter().fold(0, |acc, word| acc | get_repr(word));
+            let unused = 26 - letters.count_ones();
+            if unused != 1 {
+                problems.push(format!("{} letters unused", unused));
+            }
+        }
+        let key = solution.iter().sorted().join(" ");
+        if !seen.insert(key.clone()) {
+            problems.push("duplicate solution".to_string());
+        }
+        if !problems.is_empty() {
+            for problem in problems {
+                report
+                    .problems
+                    .push(format!("line {}: {}", line_no + 1, problem));
+            }
+            continue;
+        }
+        report.valid += 1;
+        if !expected.contains(&key) {
+            report.extra.push(key);
+        }
+    }
+    report.missing = expected
+        .iter()
+        .filter(|solution| !seen.contains(*solution))
+        .cloned()
+        .sorted()
+        .collect();
+    report
+}
+
+/// Every word-level solution `solver` finds in `words`, as sorted words joined by spaces.
+pub fn solver_solutions(words: &[String], solver: SolverFn, options: &Options) -> HashSet<String> {
+    let (reprs, keys) = build_word_representations(words);
+    let found = Mutex::new(HashSet::new());
+    solver(&keys, options, &|combo| {
+        let classes = combo.iter().map(|key| reprs[key].iter().unique());
+        let mut found = found.lock().unwrap();
+        for solution in classes.multi_cartesian_product() {
+            found.insert(solution.into_iter().sorted().join(" "));
+        }
+    });
+    found.into_inner().unwrap()
+}
+
+/// `verify <solutions> [solver]`: prints what's wrong with the solutions file, compared with a
+/// fresh run of the solver (dlx by default), and exits with status 1 if anything is.
+pub fn verify(words: &[String], args: &[String], options: &Options) {
+    let path = match args.first() {
+        Some(path) => path,
+        None => panic!("usage: <wordfile> verify <solutions> [solver]"),
+    };
+    let text = match fs::read_to_string(path) {
+        Err(why) => panic!("couldn't read {}: {}", path, why),
+        Ok(text) => text,
+    };
+    let name = args.get(1).map_or("dlx", |name| name.as_str());
+    let solver = match find_solver(name) {
+        Some(solver) => solver,
+        None => panic!("unknown solver {}", name),
+    };
+    let expected = solver_solutions(words, solver, options);
+    let report = verify_solutions(&text, words, &expected);
+    for problem in report.problems.iter() {
+        println!("{}", problem);
+    }
+    for solution in report.missing.iter() {
+        println!("missing: {}", solution);
+    }
+    for solution in report.extra.iter() {
+        println!("extra: {}", solution);
+    }
+    println!(
+        "{} solutions checked: {} valid, {} problems, {} missing, {} extra ({} expected from {})",
+        report.lines,
+        report.valid,
+        report.problems.len(),
+        report.missing.len(),
+        report.extra.len(),
+        expected.len(),
+        name
+    );
+    if !report.is_ok() {
+        std::process::exit(1);
+    }
+}
+
+#[test]
+fn test_parse_solution_line() {
+    let words = |line: &str| parse_solution_line(line).map(|words| words.join(" "));
+    let expected = Some("fjord gucks nymph vibex waltz".to_string());
+    assert_eq!(words("fjord gucks nymph vibex waltz "), expected);
+    assert_eq!(words("fjord, gucks,nymph,\"vibex\",waltz"), expected);
+    assert_eq!(
+        words("[\"fjord\", \"gucks\", \"nymph\", \"vibex\", \"waltz\"]"),
+        expected
+    );
+    assert_eq!(
+        words(
+            "{\"rank\":1,\"words\":[{\"word\":\"fjord\",\"tags\":[\"x\"]},{\"word\":\"gucks\",\
+             \"tags\":[]},{\"word\":\"nymph\",\"tags\":[]},{\"word\":\"vibex\",\"tags\":[]},\
+             {\"word\":\"waltz\",\"tags\":[]}]}"
+        ),
+        expected
+    );
+    assert_eq!(
+        words("[\"fjord\", \"gucks\", \"nymph\", \"vib\\u0065x\", \"waltz\"]"),
+        expected
+    );
+    assert_eq!(
+        words("[\"\\ud83d\\ude00\", \"\\ud83d\", \"\\u00zz\"]"),
+        Some("\u{1F600} \u{FFFD} \u{FFFD}zz".to_string())
+    );
+    assert_eq!(words("# shard 0/2 done"), None);
+    assert_eq!(words("Solutions count: 3"), None);
+}
+
+#[test]
+fn test_verify_solutions() {
+    use crate::backtracking_brute::backtracking_brute_keys;
+    use crate::word_reprs::test_words;
+
+    let words = test_words();
+    let expected = solver_solutions(&words, backtracking_brute_keys, &Options::default());
+    let all: Vec<&String> = expected.iter().sorted().collect();
+    assert!(all.len() > 2);
+
+    let text = all.iter().map(|s| s.as_str()).join("\n");
+    let report = verify_solutions(&text, &words, &expected);
+    assert!(report.is_ok());
+    assert_eq!((report.lines, report.valid), (all.len(), all.len()));
+    let header = format!("word1,word2,word3,word4,word5\n{}", text);
+    let report = verify_solutions(&header, &words, &expected);
+    assert!(report.is_ok());
+    assert_eq!((report.lines, report.valid), (all.len(), all.len()));
+
+    // Drop the first, repeat the second and add some broken ones
+    let text = format!(
+        "{}\n{}\nfjord gucks nymph vibex\nfjord gucks nymph vibex xylyl\n\
+         fjord glent nymph vibex waltz\nword1,word2,word3,word4,word5\n\
+         FJORD gucks nymph vibex waltz\n",
+        all[1..].iter().join("\n"),
+        all[1]
+    );
+    let report = verify_solutions(&text, &words, &expected);
+    assert_eq!(report.missing, vec![all[0].clone()]);
+    assert!(report.extra.is_empty());
+    let n = all.len();
+    assert_eq!(
+        report.problems,
+        vec![
+            format!("line {}: duplicate solution", n),
+            format!("line {}: 4 words", n + 1),
+            format!("line {}: 6 letters unused", n + 1),
+            format!("line {}: xylyl is not in the word list", n + 2),
+            format!("line {}: nymph and xylyl share letters", n + 2),
+            format!("line {}: vibex and xylyl share letters", n + 2),
+            format!("line {}: 5 letters unused", n + 2),
+            format!("line {}: glent and nymph share letters", n + 3),
+            format!("line {}: glent and vibex share letters", n + 3),
+            format!("line {}: glent and waltz share letters", n + 3),
+            format!("line {}: 5 letters unused", n + 3),
+            format!("line {}: word1 is not in the word list", n + 4),
+            format!("line {}: word2 is not in the word list", n + 4),
+            format!("line {}: word3 is not in the word list", n + 4),
+            format!("line {}: word4 is not in the word list", n + 4),
+            format!("line {}: word5 is not in the word list", n + 4),
+            format!("line {}: FJORD is not in the word list", n + 5),
+        ]
+    );
+}
@@ -1,16 +1,20 @@
+use itertools::Itertools;
 use std::collections::HashMap;
+use std::io::prelude::*;
 
-pub fn build_word_representations(words: &Vec<String>) -> (HashMap<u32, Vec<&str>>, Vec<u32>) {
+/// Receives each solution as five anagram-class keys (see `get_repr`).
+/// `Sync` so that parallel solvers can share a single sink.
+pub type ComboSink<'a> = dyn Fn([u32; 5]) + Sync + 'a;
+
+pub fn build_word_representations(words: &[String]) -> (HashMap<u32, Vec<&str>>, Vec<u32>) {
     let mut reprs: HashMap<u32, Vec<&str>> = HashMap::new();
     let mut keys: Vec<u32> = Vec::new();
     for word in words.iter() {
         let repr = get_repr(word);
-        if reprs.contains_key(&repr) {
-            reprs.get_mut(&repr).unwrap().push(word);
-        } else {
-            let mut v : Vec<&str> = Vec::new();
+        if let Some(v) = reprs.get_mut(&repr) {
             v.push(word);
-            reprs.insert(repr, v);
+        } else {
+            reprs.insert(repr, vec![word]);
             keys.push(repr);
         }
     }
@@ -25,4 +29,63 @@ pub fn get_repr(word: &str) -> u32 {
         res |= 1 << (val - ('a' as u32));
     }
     res
-}
\ No newline at end of file
+}
+
+/// Prints every word-level solution a combo of anagram classes stands for.
+pub fn print_combo(combo: [u32; 5], reprs: &HashMap<u32, Vec<&str>>) {
+    for word_combo in combo
+        .iter()
+        .map(|key| reprs.get(key).unwrap())
+        .multi_cartesian_product()
+    {
+        let word_combo = word_combo.into_iter().fold(String::new(), |mut acc, word| {
+            acc.push_str(word);
+            acc.push(' ');
+            acc
+        });
+        let _ = writeln!(std::io::stdout(), "{}", word_combo);
+    }
+}
+
+/// Number of word-level solutions a combo of anagram classes stands for.
+pub fn combo_word_count(combo: &[u32], reprs: &HashMap<u32, Vec<&str>>) -> usize {
+    combo
+        .iter()
+        .map(|key| reprs.get(key).unwrap().len())
+        .product()
+}
+
+/// Small word list with a few known solutions, for tests.
+#[cfg(test)]
+pub fn test_words() -> Vec<String> {
+    [
+        "fjord", "gucks", "nymph", "vibex", "waltz", "glent", "jumby", "prick", "vozhd", "waqfs",
+        "bemix", "clunk", "grypt", "vozhd", "bling", "jumpy", "treck", "waqfs", "fjeld", "chunk",
+        "vibex", "gymps", "qursh", "waltz", "crwth", "fjeld", "vibex", "zygon", "quack",
+    ]
+    .iter()
+    .map(|w| w.to_string())
+    .collect()
+}
+
+/// Runs `solver` and returns its combos, each sorted, in sorted order.
+#[cfg(test)]
+pub fn collect_sorted(solver: crate::SolverFn, keys: &[u32]) -> Vec<[u32; 5]> {
+    collect_sorted_with(solver, keys, &crate::options::Options::default())
+}
+
+#[cfg(test)]
+pub fn collect_sorted_with(
+    solver: crate::SolverFn,
+    keys: &[u32],
+    options: &crate::options::Options,
+) -> Vec<[u32; 5]> {
+    let found = std::sync::Mutex::new(Vec::new());
+    solver(keys, options, &|mut combo| {
+        combo.sort();
+        found.lock().unwrap().push(combo);
+    });
+    let mut found = found.into_inner().unwrap();
+    found.sort();
+    found
+}
@@ -0,0 +1,288 @@
+// wordle: picks the five-word solutions that make the best Wordle openers.
+//
+// Guessing all five words of a solution gives, for every possible answer, five green / yellow /
+// grey patterns. Answers with the same patterns can't be told apart afterwards, so a set is
+// scored by how it splits the answer list: the expected number of candidates left (the sum of
+// the squared group sizes over the number of answers, lower is better) or the entropy of the
+// split in bits (higher is better). Anagrams give different patterns, so every word-level
+// solution the solver streams out is scored on its own; the best --top are kept in a `TopK`.
+
+use prettytable::{Cell, Row, Table};
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::Mutex;
+
+use itertools::Itertools;
+
+use crate::lexicon::{parse_line, OutputFormat, Tags};
+use crate::options::Options;
+use crate::ranking::TopK;
+use crate::word_reprs::*;
+use crate::{find_solver, SolverFn};
+
+const DEFAULT_TOP: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WordleScore {
+    /// Expected number of answers left
+    #[default]
+    Expected,
+    /// Bits of information gained
+    Entropy,
+}
+
+impl WordleScore {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "expected" => Some(WordleScore::Expected),
+            "entropy" => Some(WordleScore::Entropy),
+            _ => None,
+        }
+    }
+}
+
+/// Feedback for `guess` against `answer` as a base 3 number, one digit per position from the
+/// first: 0 grey, 1 yellow, 2 green. A letter the answer has fewer times than the guess is
+/// yellow only as often as the answer has it left over after the greens.
+pub fn feedback(guess: &[u8; 5], answer: &[u8; 5]) -> u8 {
+    let mut digits = [0u8; 5];
+    let mut left = [0u8; 26];
+    for i in 0..5 {
+        if guess[i] == answer[i] {
+            digits[i] = 2;
+        } else {
+            left[(answer[i] - b'a') as usize] += 1;
+        }
+    }
+    for i in 0..5 {
+        let letter = (guess[i] - b'a') as usize;
+        if digits[i] == 0 && left[letter] > 0 {
+            digits[i] = 1;
+            left[letter] -= 1;
+        }
+    }
+    digits.iter().fold(0, |code, digit| code * 3 + digit)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Split {
+    pub expected: f64,
+    pub entropy: f64,
+}
+
+impl Split {
+    /// Scores the guesses whose feedback against each answer is in `patterns`.
+    pub fn of(patterns: &[&[u8]]) -> Self {
+        let answers = patterns[0].len();
+        let mut codes: Vec<u64> = (0..answers)
+            .map(|a| patterns.iter().fold(0, |code, p| code << 8 | p[a] as u64))
+            .collect();
+        codes.sort_unstable();
+        let n = answers as f64;
+        let mut split = Split {
+            expected: 0.0,
+            entropy: 0.0,
+        };
+        for group in codes.chunk_by(|a, b| a == b) {
+            let size = group.len() as f64;
+            split.expected += size * size / n;
+            split.entropy -= size / n * (size / n).log2();
+        }
+        split
+    }
+
+    // Larger is better
+    fn score(&self, by: WordleScore) -> f64 {
+        match by {
+            WordleScore::Expected => -self.expected,
+            WordleScore::Entropy => self.entropy,
+        }
+    }
+}
+
+fn as_letters(word: &str) -> Option<[u8; 5]> {
+    let letters: [u8; 5] = word.as_bytes().try_into().ok()?;
+    letters
+        .iter()
+        .all(|b| b.is_ascii_lowercase())
+        .then_some(letters)
+}
+
+/// The `top` best word-level solutions found by `solver` on `words`, as indices into `words`.
+pub fn best_openers(
+    words: &[String],
+    answers: &[[u8; 5]],
+    solver: SolverFn,
+    top: usize,
+    options: &Options,
+) -> Vec<([u32; 5], Split)> {
+    let (reprs, keys) = build_word_representations(words);
+    let index: HashMap<&str, u32> = words
+        .iter()
+        .enumerate()
+        .map(|(i, word)| (word.as_str(), i as u32))
+        .collect();
+    let found = Mutex::new(Vec::new());
+    solver(&keys, options, &|combo| found.lock().unwrap().push(combo));
+    let combos = found.into_inner().unwrap();
+    if answers.is_empty() {
+        return Vec::new();
+    }
+
+    // Feedback of each word that's in a solution, against every answer
+    let used: HashSet<u32> = combos.iter().flatten().copied().collect();
+    let patterns: HashMap<&str, Vec<u8>> = used
+        .iter()
+        .flat_map(|key| reprs[key].iter().copied())
+        .collect::<Vec<&str>>()
+        .into_par_iter()
+        .map(|word| {
+            let guess = as_letters(word).unwrap();
+            let row = answers.iter().map(|a| feedback(&guess, a)).collect();
+            (word, row)
+        })
+        .collect();
+
+    combos
+        .par_iter()
+        .fold(
+            || TopK::new(top),
+            |mut best, combo| {
+                // A word listed twice is still one guess
+                let classes = combo.iter().map(|key| reprs[key].iter().unique());
+                for words in classes.multi_cartesian_product() {
+                    let rows: Vec<&[u8]> = words.iter().map(|w| patterns[*w].as_slice()).collect();
+                    let split = Split::of(&rows);
+                    let mut ids = [0u32; 5];
+                    for (id, word) in ids.iter_mut().zip(words.iter()) {
+                        *id = index[**word];
+                    }
+                    ids.sort();
+                    best.push_with(split.score(options.wordle_score), ids, split);
+                }
+                best
+            },
+        )
+        .reduce(|| TopK::new(top), TopK::merge)
+        .into_sorted_vec()
+        .into_iter()
+        .map(|ranked| (ranked.combo, ranked.extra))
+        .collect()
+}
+
+/// `wordle [answers] [solver]`: scores the solutions as openers against the answers (the word
+/// list itself by default) and prints the best --top.
+pub fn wordle(words: Vec<String>, tags: &Tags, args: &[String], options: &Options) {
+    let answers: Vec<[u8; 5]> = match args.first() {
+        Some(path) => match fs::read_to_string(path) {
+            Err(why) => panic!("couldn't read {}: {}", path, why),
+            Ok(text) => text
+                .lines()
+                .filter_map(|line| as_letters(&parse_line(line).word))
+                .collect(),
+        },
+        None => words.iter().filter_map(|word| as_letters(word)).collect(),
+    };
+    let name = args.get(1).map_or("dlx", |name| name.as_str());
+    let solver = match find_solver(name) {
+        Some(solver) => solver,
+        None => panic!("unknown solver {}", name),
+    };
+    let top = options.top.unwrap_or(DEFAULT_TOP);
+    let best = best_openers(&words, &answers, solver, top, options);
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("rank"),
+        Cell::new("expected left"),
+        Cell::new("entropy [bits]"),
+        Cell::new("words"),
+    ]));
+    for (i, (ids, split)) in best.iter().enumerate() {
+        let words: Vec<&str> = ids.iter().map(|id| words[*id as usize].as_str()).collect();
+        if options.format == OutputFormat::Json {
+            println!(
+                "{{\"rank\":{},\"expected\":{},\"entropy\":{},\"words\":{}}}",
+                i + 1,
+                split.expected,
+                split.entropy,
+                tags.json_words(&words)
+            );
+            continue;
+        }
+        table.add_row(Row::new(vec![
+            Cell::new(&(i + 1).to_string()),
+            Cell::new(&format!("{:.3}", split.expected)),
+            Cell::new(&format!("{:.3}", split.entropy)),
+            Cell::new(&words.join(" ")),
+        ]));
+    }
+    if options.format == OutputFormat::Text {
+        eprintln!("{} answers", answers.len());
+        table.printstd();
+    }
+}
+
+#[test]
+fn test_feedback() {
+    let code = |guess: &str, answer: &str| {
+        let digits = feedback(&as_letters(guess).unwrap(), &as_letters(answer).unwrap());
+        (0..5)
+            .rev()
+            .map(|i| ['.', 'y', 'G'][(digits / 3u8.pow(i) % 3) as usize])
+            .collect::<String>()
+    };
+    assert_eq!(code("fjord", "fjord"), "GGGGG");
+    assert_eq!(code("crane", "nacre"), "yyyyG");
+    assert_eq!(code("waltz", "fjord"), ".....");
+    // One e left over for yellow, after the green one
+    assert_eq!(code("eerie", "there"), "y.y.G");
+    assert_eq!(code("speed", "abide"), "..y.y");
+}
+
+#[test]
+fn test_best_openers_match_exhaustive() {
+    use crate::backtracking_brute::backtracking_brute_keys;
+
+    let words = test_words();
+    let answers: Vec<[u8; 5]> = [
+        "fjord", "there", "abide", "nymph", "eerie", "waltz", "quack",
+    ]
+    .iter()
+    .map(|word| as_letters(word).unwrap())
+    .collect();
+    let (reprs, keys) = build_word_representations(&words);
+    let mut expected: Vec<f64> = Vec::new();
+    for combo in collect_sorted(backtracking_brute_keys, &keys) {
+        let classes = combo.iter().map(|key| reprs[key].iter().unique());
+        for guesses in classes.multi_cartesian_product() {
+            let rows: Vec<Vec<u8>> = guesses
+                .iter()
+                .map(|g| {
+                    let guess = as_letters(g).unwrap();
+                    answers.iter().map(|a| feedback(&guess, a)).collect()
+                })
+                .collect();
+            let rows: Vec<&[u8]> = rows.iter().map(|row| row.as_slice()).collect();
+            expected.push(Split::of(&rows).expected);
+        }
+    }
+    expected.sort_by(|a, b| a.total_cmp(b));
+
+    let best = best_openers(
+        &words,
+        &answers,
+        backtracking_brute_keys,
+        3,
+        &Options::default(),
+    );
+    let found: Vec<f64> = best.iter().map(|(_, split)| split.expected).collect();
+    // The same groups, added up in another order
+    assert!(found
+        .iter()
+        .zip(expected.iter())
+        .all(|(a, b)| (a - b).abs() < 1e-9));
+    assert_eq!(found.len(), 3);
+    assert!(best.iter().all(|(_, split)| split.expected > 1.0 - 1e-9));
+}